@@ -0,0 +1,23 @@
+// Turns pixels close to the key color transparent and removes its spill from the rest
+uniform sampler2D texture;
+uniform vec3 key_color;
+uniform float similarity;
+uniform float smoothness;
+uniform float spill;
+
+vec2 chroma(vec3 rgb) {
+    return vec2(
+        -0.169 * rgb.r - 0.331 * rgb.g + 0.5 * rgb.b,
+        0.5 * rgb.r - 0.419 * rgb.g - 0.081 * rgb.b
+    );
+}
+
+void main() {
+    vec4 pixel = texture2D(texture, gl_TexCoord[0].xy) * gl_Color;
+    float dist = length(chroma(pixel.rgb) - chroma(key_color));
+    float alpha = smoothstep(similarity, similarity + smoothness, dist);
+    float despill = pow(clamp((dist - similarity) / max(spill, 0.0001), 0.0, 1.0), 1.5);
+    float gray = dot(pixel.rgb, vec3(0.2126, 0.7152, 0.0722));
+    vec3 rgb = mix(vec3(gray), pixel.rgb, despill);
+    gl_FragColor = vec4(rgb, pixel.a * alpha);
+}
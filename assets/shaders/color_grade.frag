@@ -0,0 +1,17 @@
+// Brightness, contrast, saturation, gamma and a tint, in that order
+uniform sampler2D texture;
+uniform float brightness;
+uniform float contrast;
+uniform float saturation;
+uniform float gamma;
+uniform vec3 tint;
+
+void main() {
+    vec4 pixel = texture2D(texture, gl_TexCoord[0].xy) * gl_Color;
+    vec3 rgb = pixel.rgb + brightness;
+    rgb = (rgb - 0.5) * contrast + 0.5;
+    float gray = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3(gray), rgb, saturation);
+    rgb = pow(clamp(rgb, 0.0, 1.0), vec3(1.0 / max(gamma, 0.0001)));
+    gl_FragColor = vec4(rgb * tint, pixel.a);
+}
@@ -0,0 +1,21 @@
+// Scanlines, a slight screen curve and darkened corners
+uniform sampler2D texture;
+uniform vec2 resolution;
+uniform float scanlines;
+uniform float curvature;
+uniform float vignette;
+
+void main() {
+    vec2 centered = gl_TexCoord[0].xy * 2.0 - 1.0;
+    centered *= 1.0 + curvature * dot(centered.yx, centered.yx) * 0.25;
+    vec2 uv = centered * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        gl_FragColor = vec4(0.0);
+        return;
+    }
+    vec4 pixel = texture2D(texture, uv) * gl_Color;
+    float line = 0.5 + 0.5 * sin(uv.y * resolution.y * 3.14159);
+    pixel.rgb *= 1.0 - scanlines * (1.0 - line);
+    pixel.rgb *= 1.0 - vignette * dot(centered, centered) * 0.5;
+    gl_FragColor = pixel;
+}
@@ -0,0 +1,25 @@
+// A soft glow around the opaque parts of the scene, drawn behind them
+uniform sampler2D texture;
+uniform vec2 resolution;
+uniform vec4 glow_color;
+uniform float radius;
+uniform float strength;
+
+void main() {
+    vec2 uv = gl_TexCoord[0].xy;
+    vec4 pixel = texture2D(texture, uv) * gl_Color;
+    vec2 texel = radius / resolution;
+    float around = 0.0;
+    for (int ring = 1; ring <= 2; ring++) {
+        for (int i = 0; i < 12; i++) {
+            float angle = float(i) * 0.5236;
+            vec2 offset = vec2(cos(angle), sin(angle)) * texel * float(ring) * 0.5;
+            around += texture2D(texture, uv + offset).a;
+        }
+    }
+    float glow = clamp(around / 24.0 * strength, 0.0, 1.0) * glow_color.a;
+    // The pixel drawn over the glow
+    float alpha = pixel.a + glow * (1.0 - pixel.a);
+    vec3 rgb = pixel.rgb * pixel.a + glow_color.rgb * glow * (1.0 - pixel.a);
+    gl_FragColor = vec4(alpha > 0.0 ? rgb / alpha : rgb, alpha);
+}
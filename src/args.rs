@@ -1,4 +1,4 @@
-use clap::{Parser, ValueHint};
+use clap::{Parser, Subcommand, ValueEnum, ValueHint};
 use std::path::{PathBuf, MAIN_SEPARATOR};
 
 pub const DEFAULT_SKIN_DIR_NAME: &str = "skin";
@@ -25,6 +25,88 @@ pub struct Args {
     /// What config file to use
     #[arg(short, long, default_value_t = default_config(), value_hint=ValueHint::FilePath)]
     pub config: String,
+    /// Record keyboard, mouse and gamepad input to a session file
+    #[arg(long, value_hint=ValueHint::FilePath)]
+    pub record: Option<String>,
+    /// Also write every frame as video to a file or FIFO, `-` for standard output
+    #[arg(long, value_hint=ValueHint::FilePath)]
+    pub video_out: Option<String>,
+    /// Format of the --video-out stream
+    #[arg(long, value_enum, default_value_t = VideoFormat::Y4m)]
+    pub video_format: VideoFormat,
+    /// Frames per second of the --video-out stream
+    #[arg(long, default_value_t = 30)]
+    pub video_fps: u32,
+    /// Don't open a window, only write --video-out and serve the HTTP stream
+    #[arg(long)]
+    pub no_window: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Uncompressed video formats for --video-out
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 with 4:4:4 chroma, which carries its size and framerate
+    Y4m,
+    /// Bare RGBA frames with straight alpha, the reader must be told the size and framerate
+    Rgba,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Tools for working with skins
+    #[command(subcommand)]
+    Skin(SkinCommand),
+    /// Draw a recorded input session to PNG frames without opening a window
+    Render {
+        /// Session file recorded with --record
+        #[arg(short, long, value_hint=ValueHint::FilePath)]
+        input: String,
+        /// Directory to write the frames into
+        #[arg(short, long, value_hint=ValueHint::DirPath)]
+        out: String,
+        /// Frames per second of session time
+        #[arg(long, default_value_t = 60)]
+        fps: u32,
+        /// Fill the frames with the background color instead of leaving them transparent
+        #[arg(long)]
+        opaque: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SkinCommand {
+    /// Write the built-in default skin to a directory as a starting point
+    Extract {
+        /// Directory to write the skin into
+        #[arg(value_hint=ValueHint::DirPath)]
+        dir: String,
+        /// Overwrite files that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Propose anchors and a mouse pad for the skin from its images' transparency
+    Calibrate {
+        /// Where to write the before/after comparison image
+        #[arg(short, long, default_value = "calibration.png", value_hint=ValueHint::FilePath)]
+        preview: String,
+        /// Only write the comparison image, leave the config untouched
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write a copy of the skin scaled to the window and trimmed of transparent borders
+    Optimize {
+        /// Directory to write the optimized skin into
+        #[arg(value_hint=ValueHint::DirPath)]
+        out: String,
+    },
+    /// Report how much texture memory each skin uses
+    Memory {
+        /// Skin directories to report on, each with its config.toml, defaults to --skin-dir
+        #[arg(value_hint=ValueHint::DirPath)]
+        dirs: Vec<String>,
+    },
 }
 
 impl Args {
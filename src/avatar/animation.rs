@@ -0,0 +1,270 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, ImageFormat, RgbaImage};
+use sfml::system::Vector2f;
+use std::cell::Cell;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::{Margin, SfmlError, SfmlResult};
+use crate::clock;
+use crate::config::{PartSettings, Playback};
+use crate::render::{upload_image, Backend, Frame, SpriteDraw};
+
+/// Browsers treat tiny GIF delays as "as fast as possible", which is far too fast in practice
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(10);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct DecodedFrame {
+    pub image: RgbaImage,
+    pub delay: Duration,
+}
+
+/// Decoded frames of one image file, handed to the backend and shared between parts
+#[derive(Debug)]
+pub(crate) struct TextureFrames {
+    frames: Vec<Frame>,
+    delays: Vec<Duration>,
+    memory_usage: usize,
+    margin: Margin,
+}
+
+impl TextureFrames {
+    /// Hands `frames`, which were padded by `margin` to fit their effects, to `backend`
+    pub fn new(
+        frames: Vec<DecodedFrame>,
+        margin: Margin,
+        smooth: bool,
+        backend: Backend,
+    ) -> SfmlResult<Self> {
+        if frames.is_empty() {
+            return Err(SfmlError::EmptyImage);
+        }
+        let memory_usage = frames.iter().map(|frame| frame.image.as_raw().len()).sum();
+        let delays = frames.iter().map(|frame| frame.delay).collect();
+        let frames = frames
+            .into_iter()
+            .map(|frame| match backend {
+                Backend::Sfml => upload_image(&frame.image, smooth)
+                    .map(Frame::Texture)
+                    .ok_or(SfmlError::ImageCreate),
+                Backend::Software => Ok(Frame::Image {
+                    image: frame.image,
+                    smooth,
+                }),
+            })
+            .collect::<SfmlResult<Vec<_>>>()?;
+        Ok(Self {
+            frames,
+            delays,
+            memory_usage,
+            margin,
+        })
+    }
+
+    /// Bytes of image memory used by all frames
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+/// A single skin part, either a still image or an animation
+#[derive(Debug, Clone)]
+pub(crate) struct Part {
+    frames: Rc<TextureFrames>,
+    playback: Playback,
+    started: Cell<Instant>,
+    offset: Vector2f,
+    scale: Vector2f,
+    size: Vector2f,
+}
+
+impl Part {
+    pub fn new(frames: Rc<TextureFrames>, settings: &PartSettings) -> Self {
+        let scale: Vector2f = settings.scale.into_other();
+        let margin = frames.margin;
+        let offset: Vector2f = settings.offset.into_other();
+        // Effects grow the texture, keep the image itself where the settings put it
+        let offset =
+            offset - Vector2f::new(margin.left as f32 * scale.x, margin.top as f32 * scale.y);
+        let texture_size = frames.frames[0].size();
+        let image_size = Vector2f::new(
+            (texture_size.x - margin.left - margin.right) as f32,
+            (texture_size.y - margin.top - margin.bottom) as f32,
+        );
+        let size = match settings.canvas {
+            Some(canvas) => Vector2f::new(canvas.width, canvas.height),
+            None => Vector2f::new(image_size.x * scale.x, image_size.y * scale.y),
+        };
+        Self {
+            frames,
+            playback: settings.playback,
+            started: Cell::new(clock::now()),
+            offset,
+            scale,
+            size,
+        }
+    }
+
+    /// Size of the part on the skin canvas
+    pub fn size(&self) -> Vector2f {
+        self.size
+    }
+
+    /// Sprite placed on the skin canvas
+    pub fn sprite(&self) -> SpriteDraw<'_> {
+        self.sprite_with(Vector2f::new(0.0, 0.0), Vector2f::new(1.0, 1.0))
+    }
+
+    /// Sprite with `origin` and `scale` given in skin canvas units, as if the image were untrimmed
+    pub fn sprite_with(&self, origin: Vector2f, scale: Vector2f) -> SpriteDraw<'_> {
+        let mut sprite = SpriteDraw::new(self.frame());
+        sprite.scale = Vector2f::new(scale.x * self.scale.x, scale.y * self.scale.y);
+        sprite.origin = Vector2f::new(
+            (origin.x - self.offset.x) / self.scale.x,
+            (origin.y - self.offset.y) / self.scale.y,
+        );
+        sprite
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.frame_count() > 1
+    }
+
+    /// Restarts a play-once animation, called when the part becomes visible
+    pub fn trigger(&self) {
+        if self.playback == Playback::Once {
+            self.started.set(clock::now());
+        }
+    }
+
+    /// Index of the frame shown right now
+    pub fn frame_index(&self) -> usize {
+        if self.is_animated() {
+            let elapsed = clock::now().saturating_duration_since(self.started.get());
+            frame_at(&self.frames.delays, elapsed, self.playback)
+        } else {
+            0
+        }
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frames.frames[self.frame_index()]
+    }
+}
+
+fn frame_delay(numerator: u32, denominator: u32) -> Duration {
+    let delay = Duration::from_millis((numerator / denominator.max(1)).into());
+    if delay < MIN_FRAME_DELAY {
+        DEFAULT_FRAME_DELAY
+    } else {
+        delay
+    }
+}
+
+fn collect_frames(frames: image::Frames<'_>) -> SfmlResult<Vec<DecodedFrame>> {
+    let mut decoded = Vec::new();
+    for frame in frames {
+        let frame = frame?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        decoded.push(DecodedFrame {
+            delay: frame_delay(numerator, denominator),
+            image: frame.into_buffer(),
+        });
+    }
+    Ok(decoded)
+}
+
+/// Decodes a still image, animated GIF or APNG into its frames
+pub fn decode_frames(bytes: &[u8]) -> SfmlResult<Vec<DecodedFrame>> {
+    match image::guess_format(bytes)? {
+        ImageFormat::Gif => collect_frames(GifDecoder::new(Cursor::new(bytes))?.into_frames()),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes))?;
+            if decoder.is_apng() {
+                collect_frames(decoder.apng().into_frames())
+            } else {
+                let image = image::load_from_memory_with_format(bytes, ImageFormat::Png)?;
+                Ok(vec![DecodedFrame {
+                    image: image.into_rgba8(),
+                    delay: Duration::ZERO,
+                }])
+            }
+        }
+        _ => {
+            let image = image::load_from_memory(bytes)?;
+            Ok(vec![DecodedFrame {
+                image: image.into_rgba8(),
+                delay: Duration::ZERO,
+            }])
+        }
+    }
+}
+
+/// Picks the frame to show `elapsed` into an animation with the given frame delays
+pub fn frame_at(delays: &[Duration], elapsed: Duration, playback: Playback) -> usize {
+    let total: Duration = delays.iter().sum();
+    if delays.len() < 2 || total.is_zero() {
+        return 0;
+    }
+    let mut position = match playback {
+        Playback::Loop => Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64),
+        Playback::Once if elapsed >= total => return delays.len() - 1,
+        Playback::Once => elapsed,
+    };
+    for (index, delay) in delays.iter().enumerate() {
+        if position < *delay {
+            return index;
+        }
+        position -= *delay;
+    }
+    delays.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays() -> Vec<Duration> {
+        vec![
+            Duration::from_millis(100),
+            Duration::from_millis(50),
+            Duration::from_millis(100),
+        ]
+    }
+
+    #[test]
+    fn test_frame_at_loop() {
+        let delays = delays();
+        assert_eq!(0, frame_at(&delays, Duration::from_millis(0), Playback::Loop));
+        assert_eq!(1, frame_at(&delays, Duration::from_millis(120), Playback::Loop));
+        assert_eq!(2, frame_at(&delays, Duration::from_millis(150), Playback::Loop));
+        assert_eq!(0, frame_at(&delays, Duration::from_millis(260), Playback::Loop));
+    }
+
+    #[test]
+    fn test_frame_at_once_holds_last_frame() {
+        let delays = delays();
+        assert_eq!(1, frame_at(&delays, Duration::from_millis(120), Playback::Once));
+        assert_eq!(2, frame_at(&delays, Duration::from_millis(260), Playback::Once));
+        assert_eq!(2, frame_at(&delays, Duration::from_secs(60), Playback::Once));
+    }
+
+    #[test]
+    fn test_frame_at_still_image() {
+        let delays = vec![Duration::ZERO];
+        assert_eq!(0, frame_at(&delays, Duration::from_secs(1), Playback::Loop));
+    }
+
+    #[test]
+    fn test_frame_delay_clamps_tiny_delays() {
+        assert_eq!(DEFAULT_FRAME_DELAY, frame_delay(0, 1));
+        assert_eq!(Duration::from_millis(40), frame_delay(40, 1));
+    }
+}
@@ -1,19 +1,23 @@
 use log::trace;
-use sfml::graphics::{
-    CircleShape, Color, RenderTarget, RenderWindow, Shape, Sprite, Transformable,
-};
+use sfml::graphics::Color;
 use sfml::system::Vector2f;
 use std::path::Path;
 
-use super::{ArmTextures, Device, SfmlResult, TextureContainer};
+use super::{
+    segment_rotation, solve_arm, ArmPose, ArmTextures, Device, LayerStyle, Part, SfmlResult,
+    TextureContainer, TextureStore,
+};
+use crate::config::{IkArmSettings, RigPart};
 use crate::errors::Result;
+use crate::render::{Renderer, Shape, SpriteDraw};
 use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
 use crate::Config;
 use crate::KeyboardState;
 
 const TO_DEGREE: f32 = 180.0 / std::f32::consts::PI;
+const MARK_RADIUS: f32 = 5.0;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LeftArmState {
     Left,
     Right,
@@ -31,20 +35,25 @@ impl From<KeyboardState> for LeftArmState {
 }
 
 #[derive(Debug)]
-pub struct Arms<'a> {
+pub struct Arms {
     textures: ArmTextures,
-    device: Device<'a>,
+    device: Device,
     arm_offset: Vector2f,
     anchor: Vector2f,
-    hand_mark: CircleShape<'a>,
-    anchor_mark: CircleShape<'a>,
+    hand_mark: Shape,
+    anchor_mark: Shape,
+    elbow_mark: Shape,
+    left_arm_state: Option<LeftArmState>,
+    ik_arm: IkArmSettings,
+    /// Joints of the two-bone arm as of the last hand position
+    pose: Option<ArmPose>,
 }
 
-impl<'a> Arms<'a> {
-    pub fn new(images_path: &Path, config: &Config) -> SfmlResult<Self> {
-        let textures = ArmTextures::new(images_path)?;
-        let device = Device::new(images_path, config)?;
-        let (anchor_mark, hand_mark) = Self::setup_debug(config);
+impl Arms {
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let textures = ArmTextures::new(store, images_path, config)?;
+        let device = Device::new(store, images_path, config)?;
+        let (anchor_mark, hand_mark, elbow_mark) = Self::setup_debug(config);
         let arm_offset = config.anchors.arm_offset.into_other();
         let anchor = config.anchors.anchor.into_other();
         Ok(Self {
@@ -54,97 +63,200 @@ impl<'a> Arms<'a> {
             anchor,
             anchor_mark,
             hand_mark,
+            elbow_mark,
+            left_arm_state: None,
+            ik_arm: config.ik_arm.clone(),
+            pose: None,
         })
     }
 
-    fn setup_debug(config: &Config) -> (CircleShape<'a>, CircleShape<'a>) {
-        let mut anchor_mark = CircleShape::default();
-        let mut hand_mark = CircleShape::default();
-        anchor_mark.set_position(config.anchors.anchor.into_other());
-        anchor_mark.set_radius(5.0);
-        anchor_mark.set_fill_color(Color::BLUE);
+    fn setup_debug(config: &Config) -> (Shape, Shape, Shape) {
+        let mut anchor_mark = Shape::circle(MARK_RADIUS);
+        let mut hand_mark = Shape::circle(MARK_RADIUS);
+        let mut elbow_mark = Shape::circle(MARK_RADIUS);
+        anchor_mark.position = config.anchors.anchor.into_other();
+        anchor_mark.origin = Vector2f::new(MARK_RADIUS, MARK_RADIUS);
+        anchor_mark.fill = Color::BLUE;
+
+        hand_mark.origin = Vector2f::new(MARK_RADIUS, MARK_RADIUS);
+        hand_mark.fill = Color::RED;
+
+        elbow_mark.origin = Vector2f::new(MARK_RADIUS, MARK_RADIUS);
+        elbow_mark.fill = Color::GREEN;
+        (anchor_mark, hand_mark, elbow_mark)
+    }
 
-        hand_mark.set_radius(5.0);
-        hand_mark.set_fill_color(Color::RED);
-        (anchor_mark, hand_mark)
+    pub fn update_config(&mut self, store: &mut TextureStore, config: &Config) -> Result<()> {
+        self.textures
+            .reload_textures(store, &config.images_path, config)?;
+        self.left_arm_state = None;
+        self.device.update_config(store, config)?;
+        self.update_calibration(config);
+        Ok(())
     }
 
-    pub fn update_config(&mut self, config: &Config) -> Result<()> {
+    /// Picks up changed anchors and mouse pad without reloading any texture
+    pub fn update_calibration(&mut self, config: &Config) {
         self.arm_offset = config.anchors.arm_offset.into_other();
         self.anchor = config.anchors.anchor.into_other();
-        let (anchor_mark, hand_mark) = Self::setup_debug(config);
+        self.ik_arm = config.ik_arm.clone();
+        self.pose = None;
+        let (anchor_mark, hand_mark, elbow_mark) = Self::setup_debug(config);
         self.anchor_mark = anchor_mark;
         self.hand_mark = hand_mark;
-        self.textures.reload_textures(&config.images_path)?;
-        self.device.update_config(config)?;
-        Ok(())
+        self.elbow_mark = elbow_mark;
+        self.device.update_mouse_mark(config);
     }
 
-    pub fn right_arm_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.right)
+    /// Restarts the play-once images of `part`, those of the hand and mouse on their next draw
+    pub fn restart(&mut self, part: RigPart) {
+        match part {
+            RigPart::Mouse => self.device.restart(),
+            RigPart::RightArm => {
+                self.textures.right.trigger();
+                if let Some((upper, forearm)) = &self.textures.segments {
+                    upper.trigger();
+                    forearm.trigger();
+                }
+            }
+            RigPart::LeftArm => self.left_arm_state = None,
+            RigPart::Background | RigPart::Avatar => {}
+        }
     }
 
-    pub fn left_arm_left_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.left.left)
+    pub fn parts(&self) -> Vec<&Part> {
+        let mut parts = self.textures.parts();
+        parts.extend(self.device.parts());
+        parts
     }
 
-    pub fn left_arm_right_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.left.right)
+    pub fn left_arm_left_sprite(&self) -> SpriteDraw {
+        self.textures.left.left.sprite()
     }
 
-    pub fn left_arm_up_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.left.up)
+    pub fn left_arm_right_sprite(&self) -> SpriteDraw {
+        self.textures.left.right.sprite()
     }
 
-    fn get_right_arm(&self, hand_pos: Vector2f) -> Sprite {
-        let arm_origin = self.arm_offset;
-        let mut arm = self.right_arm_sprite();
-        arm.set_origin(arm_origin);
-        arm.set_position(self.anchor);
+    pub fn left_arm_up_sprite(&self) -> SpriteDraw {
+        self.textures.left.up.sprite()
+    }
 
-        let displacement = hand_pos - self.anchor_mark.position();
+    fn get_right_arm(&self, hand_pos: Vector2f) -> SpriteDraw {
+        let part = &self.textures.right;
+        let displacement = hand_pos - self.anchor_mark.position;
         let dist = displacement.x.hypot(displacement.y);
-        let arm_bounds = arm.local_bounds();
-        let scale = dist / arm_bounds.height;
+        let scale = dist / part.size().y;
 
-        arm.set_scale((1.0, scale));
+        let mut arm = part.sprite_with(self.arm_offset, Vector2f::new(1.0, scale));
+        arm.position = self.anchor;
 
         let alpha = (-displacement.x / dist).asin();
         let deg = alpha * TO_DEGREE;
 
-        arm.set_rotation(deg);
-        arm.clone()
+        arm.rotation = deg;
+        arm
     }
 
-    pub fn draw_right_arm(
+    /// Upper arm and forearm drawn at the solved joints
+    fn get_segments(&self) -> Option<[SpriteDraw; 2]> {
+        let pose = self.pose.as_ref()?;
+        let (upper, forearm) = self.textures.segments.as_ref()?;
+        let unit = Vector2f::new(1.0, 1.0);
+        let mut upper = upper.sprite_with(self.ik_arm.shoulder_joint.into_other(), unit);
+        upper.position = pose.shoulder;
+        upper.rotation = segment_rotation(pose.shoulder, pose.elbow);
+        let mut forearm = forearm.sprite_with(self.ik_arm.elbow_joint.into_other(), unit);
+        forearm.position = pose.elbow;
+        forearm.rotation = segment_rotation(pose.elbow, pose.hand);
+        Some([upper, forearm])
+    }
+
+    /// Where the hand holding the mouse is on the canvas, as close to the cursor as the arm reaches
+    fn hand_position(&mut self, mouse_pos: Vector2f) -> Vector2f {
+        trace!("Mouse Pos{{ X: {}, Y: {} }}", mouse_pos.x, mouse_pos.y);
+        let transform = { self.device.get_hand_transform() };
+
+        let (x, y) = transform.apply((mouse_pos.x, mouse_pos.y));
+        let mut hand_pos = Vector2f::new(x, y);
+        if self.textures.segments.is_some() {
+            let pose = solve_arm(self.anchor, hand_pos, &self.ik_arm);
+            hand_pos = pose.hand;
+            self.elbow_mark.position = pose.elbow;
+            self.pose = Some(pose);
+        }
+        trace!("Hand Pos{{ X: {}, Y: {} }}", hand_pos.x, hand_pos.y);
+
+        self.hand_mark.position = hand_pos;
+        hand_pos
+    }
+
+    pub fn draw_mouse(
         &mut self,
         mouse_pos: Vector2f,
-        window: &mut RenderWindow,
+        renderer: &mut dyn Renderer,
         mouse: &DeviceViewModelImpl,
+        style: &LayerStyle,
     ) {
-        trace!("Mouse Pos{{ X: {}, Y: {} }}", mouse_pos.x, mouse_pos.y);
-        let transform = { self.device.get_hand_transform() };
+        let hand_pos = self.hand_position(mouse_pos);
+        self.device.draw(hand_pos, renderer, mouse, style);
+    }
 
-        let hand_pos = transform.transform_point(mouse_pos);
-        trace!("Hand Pos{{ X: {}, Y: {} }}", hand_pos.x, hand_pos.y);
+    pub fn draw_right_arm(
+        &mut self,
+        mouse_pos: Vector2f,
+        renderer: &mut dyn Renderer,
+        style: &LayerStyle,
+    ) {
+        let hand_pos = self.hand_position(mouse_pos);
+        if let Some(segments) = self.get_segments() {
+            for segment in segments {
+                style.draw(renderer, segment);
+            }
+            return;
+        }
+        let arm = self.get_right_arm(hand_pos);
+        style.draw(renderer, arm)
+    }
 
-        self.hand_mark.set_position(hand_pos);
-        self.device.draw(hand_pos, window, mouse);
-        window.draw(&self.get_right_arm(hand_pos))
+    fn left_arm_part(&self, state: LeftArmState) -> &Part {
+        match state {
+            LeftArmState::Up => &self.textures.left.up,
+            LeftArmState::Left => &self.textures.left.left,
+            LeftArmState::Right => &self.textures.left.right,
+        }
     }
 
-    pub fn draw_left_arm(&mut self, window: &mut RenderWindow, keyboard: &KeyboardViewModelImpl) {
-        let sprite = match keyboard.keyboard_state().into() {
+    pub fn draw_left_arm(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        keyboard: &KeyboardViewModelImpl,
+        style: &LayerStyle,
+    ) {
+        let state: LeftArmState = keyboard.keyboard_state().into();
+        if self.left_arm_state != Some(state) {
+            self.left_arm_part(state).trigger();
+            self.left_arm_state = Some(state);
+        }
+        let sprite = match state {
             LeftArmState::Up => self.left_arm_up_sprite(),
             LeftArmState::Left => self.left_arm_left_sprite(),
             LeftArmState::Right => self.left_arm_right_sprite(),
         };
-        window.draw(&sprite)
+        style.draw(renderer, sprite)
+    }
+
+    /// Hand position on the canvas as of the last drawn frame
+    pub fn last_hand_position(&self) -> Vector2f {
+        self.hand_mark.position
     }
 
-    pub fn draw_debug(&self, window: &mut RenderWindow) {
-        window.draw(&self.hand_mark);
-        window.draw(&self.anchor_mark);
-        self.device.draw_debug(window)
+    pub fn draw_debug(&self, renderer: &mut dyn Renderer) {
+        renderer.draw_shape(&self.hand_mark);
+        renderer.draw_shape(&self.anchor_mark);
+        if self.pose.is_some() {
+            renderer.draw_shape(&self.elbow_mark);
+        }
+        self.device.draw_debug(renderer)
     }
 }
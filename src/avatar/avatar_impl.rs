@@ -1,81 +1,291 @@
-use sfml::graphics::{RenderTarget, RenderWindow, Sprite};
+use sfml::system::Vector2f;
 use std::path::Path;
 
-use super::{Arms, AvatarTextures, TextureContainer};
+use log::{debug, warn};
+
+use super::{
+    skin_textures, Arms, AvatarTextures, HandSmoother, LayerStyle, Part, TextureContainer,
+    TextureStore,
+};
+use crate::clock;
+use crate::config::{LayerSource, RigPart};
 use crate::errors::Result;
-use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::expression::Expression;
+use crate::render::{Renderer, SpriteDraw};
+use crate::view_models::{
+    DeviceViewModelImpl, InputSnapshot, KeyboardState, KeyboardViewModelImpl, MouseButtonState,
+};
 use crate::Config;
 
 #[derive(Debug)]
-pub(crate) struct Avatar<'a> {
+pub(crate) struct Avatar {
+    store: TextureStore,
     textures: AvatarTextures,
-    arms: Arms<'a>,
+    arms: Arms,
+    /// Filters the cursor position the mouse hand follows
+    hand: HandSmoother,
+    layers: Vec<(LayerSource, LayerStyle, Expression)>,
+    /// Whether each layer was drawn last frame, to restart what comes back into view
+    shown: Vec<bool>,
     config: Config,
 }
 
-impl<'a> Avatar<'a> {
-    pub fn new(image_path: &Path, config: Config) -> Result<Self> {
-        let textures = AvatarTextures::new(image_path)?;
-        let arms = Arms::new(image_path, &config)?;
+/// Everything a frame depends on, a frame equal to the last one drawn can be skipped
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FrameState {
+    mouse: Vector2f,
+    /// Still changes while the hand catches up with a cursor at rest
+    hand: Vector2f,
+    buttons: MouseButtonState,
+    keyboard: KeyboardState,
+    visible: Vec<bool>,
+    frames: Vec<usize>,
+}
+
+/// A layer whose condition does not parse is never drawn
+fn layer_condition(when: Option<&str>) -> Expression {
+    match when {
+        None => Expression::Constant(true),
+        Some(source) => source.parse().unwrap_or_else(|e| {
+            warn!("Hiding layer, invalid condition {:?}: {}", source, e);
+            Expression::Constant(false)
+        }),
+    }
+}
+
+fn layer_styles(config: &Config) -> Vec<(LayerSource, LayerStyle, Expression)> {
+    config
+        .layers()
+        .into_iter()
+        .map(|layer| {
+            let style = LayerStyle::new(&layer);
+            let condition = layer_condition(layer.when.as_deref());
+            (layer.source, style, condition)
+        })
+        .collect()
+}
+
+/// Restarts the play-once parts of a layer that just came into view
+fn restart_layer(source: &LayerSource, textures: &AvatarTextures, arms: &mut Arms) {
+    match source {
+        LayerSource::Part { part } => match part {
+            RigPart::Background => {
+                if let Some(background) = &textures.background {
+                    background.trigger();
+                }
+            }
+            RigPart::Avatar => textures.avatar.trigger(),
+            _ => arms.restart(*part),
+        },
+        LayerSource::Image { image } => {
+            if let Some(overlay) = textures.overlays.get(image) {
+                overlay.trigger();
+            }
+        }
+    }
+}
+
+impl Avatar {
+    /// Builds the rig, taking textures from `store` and loading any it is missing
+    pub fn new(mut store: TextureStore, image_path: &Path, config: Config) -> Result<Self> {
+        let textures = AvatarTextures::new(&mut store, image_path, &config)?;
+        let arms = Arms::new(&mut store, image_path, &config)?;
+        debug!("Texture memory: {} bytes", store.memory_usage());
+        let layers = layer_styles(&config);
 
         Ok(Self {
+            store,
             textures,
             arms,
+            hand: HandSmoother::new(&config.smoothing),
+            shown: vec![false; layers.len()],
+            layers,
             config,
         })
     }
 
-    pub fn update_config(&mut self, config: Config) -> Result<()> {
-        self.textures.reload_textures(&config.images_path)?;
-        self.arms.update_config(&config)?;
+    pub fn update_config<F>(&mut self, config: Config, on_progress: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.store.set_smooth(!config.window.pixel_art);
+        self.store
+            .preload(&config.images_path, &skin_textures(&config), on_progress)?;
+        self.textures
+            .reload_textures(&mut self.store, &config.images_path, &config)?;
+        self.arms.update_config(&mut self.store, &config)?;
+        self.store.prune();
+        debug!("Texture memory: {} bytes", self.store.memory_usage());
+        self.layers = layer_styles(&config);
+        self.shown = vec![false; self.layers.len()];
+        if config.smoothing != self.config.smoothing {
+            self.hand = HandSmoother::new(&config.smoothing);
+        }
         self.config = config;
         Ok(())
     }
 
+    /// Takes over changed anchors and mouse pad from `config`, other changes need `update_config`
+    pub fn update_calibration(&mut self, config: Config) {
+        self.arms.update_calibration(&config);
+        self.config = config;
+    }
+
+    pub fn texture_store(&self) -> &TextureStore {
+        &self.store
+    }
+
     pub fn config(&self) -> &Config {
         &self.config
     }
 
-    pub fn background_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.background)
+    pub fn background_sprite(&self) -> Option<SpriteDraw> {
+        self.textures.background.as_ref().map(|background| background.sprite())
     }
 
-    pub fn avatar_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.avatar)
+    pub fn avatar_sprite(&self) -> SpriteDraw {
+        self.textures.avatar.sprite()
+    }
+
+    pub fn hand_position(&self) -> Vector2f {
+        self.arms.last_hand_position()
+    }
+
+    /// Marks the anchor, hand and mouse area
+    pub fn draw_debug(&self, renderer: &mut dyn Renderer) {
+        self.arms.draw_debug(renderer);
+    }
+
+    pub fn frame_state(
+        &self,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+    ) -> FrameState {
+        let input = InputSnapshot::capture(keyboard, mouse);
+        FrameState {
+            mouse: mouse.position(),
+            hand: self.hand.position(),
+            buttons: mouse.button_state(),
+            keyboard: keyboard.keyboard_state(),
+            visible: self
+                .layers
+                .iter()
+                .map(|(_, _, condition)| condition.evaluate(&input))
+                .collect(),
+            frames: self
+                .textures
+                .parts()
+                .into_iter()
+                .chain(self.arms.parts())
+                .map(Part::frame_index)
+                .collect(),
+        }
     }
 
     pub fn draw(
         &mut self,
-        window: &mut RenderWindow,
+        renderer: &mut dyn Renderer,
         keyboard: &KeyboardViewModelImpl,
         mouse: &DeviceViewModelImpl,
     ) -> Result<()> {
-        {
-            let bg = self.background_sprite();
-            window.draw(&bg);
-        }
-        let mouse_pos = mouse.position();
-        if self.config.avatar_below_arm {
-            {
-                let avatar = self.avatar_sprite();
-                window.draw(&avatar);
+        let mouse_pos = self.hand.update(mouse.position(), clock::now());
+        let input = InputSnapshot::capture(keyboard, mouse);
+        let visible: Vec<bool> = self
+            .layers
+            .iter()
+            .map(|(_, _, condition)| condition.evaluate(&input))
+            .collect();
+        let shown = std::mem::replace(&mut self.shown, visible.clone());
+        for (((source, style, _), visible), shown) in self.layers.iter().zip(visible).zip(shown) {
+            if !visible {
+                continue;
             }
-            self.arms.draw_right_arm(mouse_pos, window, mouse);
-        } else {
-            self.arms.draw_right_arm(mouse_pos, window, mouse);
-
-            {
-                let avatar = self.avatar_sprite();
-                window.draw(&avatar);
+            if !shown {
+                restart_layer(source, &self.textures, &mut self.arms);
+            }
+            match source {
+                LayerSource::Part { part } => match part {
+                    RigPart::Background => {
+                        if let Some(bg) = self.background_sprite() {
+                            style.draw(renderer, bg);
+                        }
+                    }
+                    RigPart::Avatar => style.draw(renderer, self.avatar_sprite()),
+                    RigPart::Mouse => self.arms.draw_mouse(mouse_pos, renderer, mouse, style),
+                    RigPart::RightArm => self.arms.draw_right_arm(mouse_pos, renderer, style),
+                    RigPart::LeftArm => self.arms.draw_left_arm(renderer, keyboard, style),
+                },
+                LayerSource::Image { image } => {
+                    if let Some(overlay) = self.textures.overlays.get(image) {
+                        style.draw(renderer, overlay.sprite());
+                    }
+                }
             }
         }
 
-        self.arms.draw_left_arm(window, keyboard);
+        Ok(())
+    }
+}
 
-        if self.config.debug {
-            self.arms.draw_debug(window);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, RgbaImage};
+    use std::fs::{self, File};
+    use std::time::{Duration, Instant};
 
-        Ok(())
+    use crate::avatar::software_avatar;
+    use crate::config::{Layer, PartSettings, Playback};
+    use crate::models::DeviceButton;
+    use crate::render::SoftwareRenderer;
+    use crate::user_input::DeviceEvent;
+
+    #[test]
+    fn test_layer_restarts_play_once_part_when_shown_again() {
+        let dir = std::env::temp_dir().join(format!("ptuber_layer_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let frames = (0..3).map(|_| {
+            let delay = Delay::from_numer_denom_ms(100, 1);
+            Frame::from_parts(RgbaImage::new(4, 4), 0, 0, delay)
+        });
+        let mut encoder = GifEncoder::new(File::create(dir.join("prop.gif")).unwrap());
+        encoder.encode_frames(frames).unwrap();
+        drop(encoder);
+
+        let mut config = Config::default();
+        config.images_path = dir.clone();
+        config.layers = vec![Layer {
+            source: LayerSource::Image {
+                image: "prop.gif".to_string(),
+            },
+            when: Some("mouse:left".to_string()),
+            ..Layer::part(RigPart::Avatar)
+        }];
+        let once = PartSettings {
+            playback: Playback::Once,
+            ..Default::default()
+        };
+        config.parts.insert("prop".to_string(), once);
+
+        clock::set_frozen(Some(Instant::now()));
+        let mut avatar = software_avatar(config).unwrap();
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        let keyboard = KeyboardViewModelImpl::new();
+        let mouse = DeviceViewModelImpl::new();
+        let frame = |avatar: &Avatar| avatar.textures.overlays["prop.gif"].frame_index();
+        let step = |time: u64| clock::set_frozen(Some(clock::now() + Duration::from_secs(time)));
+        for _ in 0..2 {
+            mouse.handle_event(&DeviceEvent::ButtonPressed(DeviceButton::Left));
+            avatar.draw(&mut renderer, &keyboard, &mouse).unwrap();
+            assert_eq!(0, frame(&avatar));
+            step(1);
+            assert_eq!(2, frame(&avatar));
+            mouse.handle_event(&DeviceEvent::ButtonReleased(DeviceButton::Left));
+            avatar.draw(&mut renderer, &keyboard, &mouse).unwrap();
+            step(1);
+        }
+        clock::set_frozen(None);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
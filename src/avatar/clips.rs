@@ -0,0 +1,154 @@
+use log::{info, warn};
+use sfml::graphics::Color;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Avatar, SceneCapture, SceneOutput};
+use crate::config::ClipFormat;
+use crate::errors::Result;
+use crate::render::{save_clip, save_png, RenderResult, ReplayBuffer};
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::Config;
+
+/// What the screenshot hotkeys and the control endpoints ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipAction {
+    /// Save the current frame as a PNG
+    Screenshot,
+    /// Save the replay buffer as an animation
+    Replay,
+}
+
+/// A file name that sorts by when it was taken
+fn file_name(kind: &str, extension: &str) -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("{}-{}.{}", kind, millis, extension)
+}
+
+/// Encodes and writes on a thread of its own so drawing doesn't stall
+fn save_in_background<F>(folder: &Path, name: String, save: F)
+where
+    F: 'static + Send + FnOnce(&Path) -> RenderResult<()>,
+{
+    let path = folder.join(name);
+    let folder = folder.to_path_buf();
+    thread::spawn(move || {
+        let saved = fs::create_dir_all(&folder)
+            .map_err(Into::into)
+            .and_then(|_| save(&path));
+        match saved {
+            Ok(()) => info!("Saved {}", path.display()),
+            Err(err) => warn!("Could not save {}: {:?}", path.display(), err),
+        }
+    });
+}
+
+/// Keeps the replay buffer filled and saves screenshots and clips when asked
+pub struct ClipRecorder {
+    folder: PathBuf,
+    format: ClipFormat,
+    /// `None` when the replay is turned off
+    replay: Option<ReplayBuffer>,
+    interval: Duration,
+    next: Instant,
+    scene: SceneCapture,
+    actions: Receiver<ClipAction>,
+    control: Sender<ClipAction>,
+}
+
+impl fmt::Debug for ClipRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClipRecorder")
+            .field("folder", &self.folder)
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClipRecorder {
+    pub fn new(config: &Config) -> Self {
+        let settings = &config.clips;
+        let (control, actions) = mpsc::channel();
+        Self {
+            folder: settings.folder_path(&config.config_path),
+            format: settings.replay_format,
+            replay: (settings.replay_seconds > 0)
+                .then(|| ReplayBuffer::new(settings.replay_length())),
+            interval: settings.frame_interval(),
+            next: Instant::now(),
+            scene: Default::default(),
+            actions,
+            control,
+        }
+    }
+
+    /// Sends actions to the recorder from anywhere, they are carried out on the next frame
+    pub fn control(&self) -> Sender<ClipAction> {
+        self.control.clone()
+    }
+
+    fn save_replay(&self, now: Instant) {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => {
+                warn!("The replay is off, set clips.replay_seconds to keep one");
+                return;
+            }
+        };
+        let frames = replay.clip(now);
+        let format = self.format;
+        let extension = match format {
+            ClipFormat::Gif => "gif",
+            ClipFormat::Apng => "png",
+        };
+        save_in_background(&self.folder, file_name("replay", extension), move |path| {
+            save_clip(&frames, format, path)
+        });
+    }
+}
+
+impl SceneOutput for ClipRecorder {
+    fn capture(
+        &mut self,
+        avatar: &mut Avatar,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+        now: Instant,
+    ) -> Result<()> {
+        let actions: Vec<ClipAction> = self.actions.try_iter().collect();
+        let screenshot = actions.contains(&ClipAction::Screenshot);
+        let record = self.replay.is_some() && now >= self.next;
+        if record || screenshot {
+            let frame = Arc::new(self.scene.draw(avatar, keyboard, mouse, background)?);
+            if let (true, Some(replay)) = (record, &mut self.replay) {
+                self.next = now + self.interval;
+                replay.push(now, frame.clone());
+            }
+            if screenshot {
+                save_in_background(&self.folder, file_name("screenshot", "png"), move |path| {
+                    save_png(&frame, path)
+                });
+            }
+        }
+        if actions.contains(&ClipAction::Replay) {
+            self.save_replay(now);
+        }
+        Ok(())
+    }
+
+    fn wait(&self, now: Instant) -> Duration {
+        match self.replay {
+            Some(_) => self.next.saturating_duration_since(now),
+            None => Duration::MAX,
+        }
+    }
+}
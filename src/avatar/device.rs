@@ -1,15 +1,14 @@
-use sfml::graphics::{
-    Color, RectangleShape, RenderTarget, RenderWindow, Shape, Sprite, Transform, Transformable,
-};
+use sfml::graphics::Color;
 use sfml::system::Vector2f;
 use std::path::Path;
 
-use super::{MouseTextures, SfmlResult, TextureContainer};
+use super::{LayerStyle, MouseTextures, Part, SfmlResult, TextureContainer, TextureStore};
 use crate::errors::Result;
+use crate::render::{Affine, Renderer, Shape, SpriteDraw};
 use crate::view_models::DeviceViewModelImpl;
 use crate::{Config, MouseButtonState};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum MouseState {
     None,
     Left,
@@ -29,16 +28,17 @@ impl From<MouseButtonState> for MouseState {
 }
 
 #[derive(Debug)]
-pub struct Device<'a> {
+pub struct Device {
     textures: MouseTextures,
     mouse_scale: Vector2f,
-    mouse_mark: RectangleShape<'a>,
+    mouse_mark: Shape,
     mouse_rotation: f32,
+    mouse_state: Option<MouseState>,
 }
 
-impl<'a> Device<'a> {
-    pub fn new(images_path: &Path, config: &Config) -> SfmlResult<Self> {
-        let textures = MouseTextures::new(images_path)?;
+impl Device {
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let textures = MouseTextures::new(store, images_path, config)?;
         let mouse_scale = config.mouse_scale.into_other();
         let mouse_mark = Self::setup_debug(config);
         let mouse_rotation = config.mouse_mark.rotation.into();
@@ -47,86 +47,90 @@ impl<'a> Device<'a> {
             mouse_scale,
             mouse_mark,
             mouse_rotation,
+            mouse_state: None,
         })
     }
 
-    fn setup_debug(config: &Config) -> RectangleShape<'a> {
-        let mut mouse_mark = RectangleShape::default();
-        mouse_mark.set_fill_color(Color::TRANSPARENT);
-        mouse_mark.set_outline_color(Color::YELLOW);
-        mouse_mark.set_outline_thickness(2.0);
-        mouse_mark.set_position(config.mouse_mark.position.into_other());
-        mouse_mark.set_size(config.mouse_mark.size.into_other());
-        mouse_mark.set_rotation(config.mouse_mark.rotation.into());
+    fn setup_debug(config: &Config) -> Shape {
+        let mut mouse_mark = Shape::rectangle(config.mouse_mark.size.into_other());
+        mouse_mark.fill = Color::TRANSPARENT;
+        mouse_mark.outline = Color::YELLOW;
+        mouse_mark.outline_thickness = 2.0;
+        mouse_mark.position = config.mouse_mark.position.into_other();
+        mouse_mark.rotation = config.mouse_mark.rotation.into();
 
         mouse_mark
     }
 
-    pub fn update_config(&mut self, config: &Config) -> Result<()> {
+    pub fn update_config(&mut self, store: &mut TextureStore, config: &Config) -> Result<()> {
         self.mouse_scale = config.mouse_scale.into_other();
-        self.mouse_rotation = config.mouse_mark.rotation.into();
-        self.textures.reload_textures(&config.images_path)?;
-        self.mouse_mark = Self::setup_debug(config);
+        self.textures
+            .reload_textures(store, &config.images_path, config)?;
+        self.mouse_state = None;
+        self.update_mouse_mark(config);
         Ok(())
     }
 
-    pub fn mouse_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.mouse)
+    /// Picks up a moved, resized or rotated `mouse_mark`
+    pub fn update_mouse_mark(&mut self, config: &Config) {
+        self.mouse_rotation = config.mouse_mark.rotation.into();
+        self.mouse_mark = Self::setup_debug(config);
     }
 
-    pub fn mouse_l_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.mouse_l)
-    }
+    pub fn get_hand_transform(&mut self) -> Affine {
+        let mouse_mark_pos = self.mouse_mark.position;
+        let mouse_mark_size = self.mouse_mark.size;
 
-    pub fn mouse_r_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.mouse_r)
+        Affine::sprite(
+            (mouse_mark_pos.x, mouse_mark_pos.y),
+            (0.0, 0.0),
+            (mouse_mark_size.x, mouse_mark_size.y),
+            self.mouse_rotation,
+        )
     }
 
-    pub fn mouse_lr_sprite(&self) -> Sprite {
-        Sprite::with_texture(&self.textures.mouse_lr)
+    /// Restarts a play-once mouse the next time it is drawn
+    pub fn restart(&mut self) {
+        self.mouse_state = None;
     }
 
-    pub fn get_hand_transform(&mut self) -> Transform {
-        let mut transform: Transform = Default::default();
-
-        let mouse_mark_pos = self.mouse_mark.position();
-        let mouse_mark_size = self.mouse_mark.size();
-
-        transform.translate(mouse_mark_pos.x, mouse_mark_pos.y);
-        transform.rotate(self.mouse_rotation);
-        transform.scale(mouse_mark_size.x, mouse_mark_size.y);
+    pub fn parts(&self) -> Vec<&Part> {
+        self.textures.parts()
+    }
 
-        transform
+    fn device_part(&self, mouse_state: &MouseState) -> &Part {
+        match mouse_state {
+            MouseState::None => &self.textures.mouse,
+            MouseState::Left => &self.textures.mouse_l,
+            MouseState::Right => &self.textures.mouse_r,
+            MouseState::Both => &self.textures.mouse_lr,
+        }
     }
 
-    fn setup_device(&self, mouse_state: &MouseState) -> Sprite<'_> {
-        let mut device = match mouse_state {
-            MouseState::None => self.mouse_sprite(),
-            MouseState::Left => self.mouse_l_sprite(),
-            MouseState::Right => self.mouse_r_sprite(),
-            MouseState::Both => self.mouse_lr_sprite(),
-        };
-        let device_scale = self.mouse_scale;
-        device.set_scale(device_scale);
-        let bounds = device.local_bounds();
-        device.set_origin(Vector2f::new(bounds.width / 2.0, bounds.height / 2.0));
-
-        device
+    fn setup_device(&self, mouse_state: &MouseState) -> SpriteDraw<'_> {
+        let part = self.device_part(mouse_state);
+        let size = part.size();
+        part.sprite_with(Vector2f::new(size.x / 2.0, size.y / 2.0), self.mouse_scale)
     }
 
     pub fn draw(
         &mut self,
         hand_pos: Vector2f,
-        window: &mut RenderWindow,
+        renderer: &mut dyn Renderer,
         mouse: &DeviceViewModelImpl,
+        style: &LayerStyle,
     ) {
         let state = mouse.button_state().into();
+        if self.mouse_state != Some(state) {
+            self.device_part(&state).trigger();
+            self.mouse_state = Some(state);
+        }
         let mut device = self.setup_device(&state);
-        device.set_position(hand_pos);
-        window.draw(&device)
+        device.position = hand_pos;
+        style.draw(renderer, device)
     }
 
-    pub fn draw_debug(&self, window: &mut RenderWindow) {
-        window.draw(&self.mouse_mark)
+    pub fn draw_debug(&self, renderer: &mut dyn Renderer) {
+        renderer.draw_shape(&self.mouse_mark)
     }
 }
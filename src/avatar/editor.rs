@@ -0,0 +1,294 @@
+use sfml::graphics::Color;
+use sfml::system::Vector2f;
+
+use crate::render::{Renderer, Shape};
+use crate::skin::Calibration;
+use crate::Config;
+
+/// How close, in canvas pixels, the cursor has to be to grab a point handle
+const GRAB_RADIUS: f32 = 10.0;
+/// Distance of the rotate handle above the mouse pad's top edge
+const ROTATE_HANDLE_DISTANCE: f32 = 24.0;
+const HANDLE_SIZE: f32 = 8.0;
+const MIN_PAD_SIZE: f32 = 1.0;
+
+/// Something on the canvas that can be dragged to change the calibration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    /// The blue mark, `anchors.anchor`
+    Anchor,
+    /// The red mark, slides the arm along `anchors.arm_offset`
+    Hand,
+    /// Inside the yellow rectangle, `mouse_mark.position`
+    MousePad,
+    /// Bottom right corner of the yellow rectangle, `mouse_mark.size`
+    MouseResize,
+    /// Above the yellow rectangle, `mouse_mark.rotation`
+    MouseRotate,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    handle: Handle,
+    from: (f32, f32),
+    start: Calibration,
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Degrees of the direction from `center` to `point`
+fn angle(center: (f32, f32), point: (f32, f32)) -> f32 {
+    (point.1 - center.1).atan2(point.0 - center.0).to_degrees()
+}
+
+/// Dragging the debug marks around, with undo and redo
+#[derive(Debug, Clone)]
+pub struct CalibrationEditor {
+    current: Calibration,
+    undo: Vec<Calibration>,
+    redo: Vec<Calibration>,
+    drag: Option<Drag>,
+}
+
+impl CalibrationEditor {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            current: Calibration::from_config(config),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            drag: None,
+        }
+    }
+
+    pub fn current(&self) -> &Calibration {
+        &self.current
+    }
+
+    fn resize_handle(&self) -> (f32, f32) {
+        self.current
+            .mouse_mark_transform()
+            .apply(self.current.mouse_mark_size)
+    }
+
+    fn rotate_handle(&self) -> (f32, f32) {
+        let width = self.current.mouse_mark_size.0;
+        self.current
+            .mouse_mark_transform()
+            .apply((width / 2.0, -ROTATE_HANDLE_DISTANCE))
+    }
+
+    fn on_mouse_pad(&self, point: (f32, f32)) -> bool {
+        let inverse = match self.current.mouse_mark_transform().inverse() {
+            Some(inverse) => inverse,
+            None => return false,
+        };
+        let (x, y) = inverse.apply(point);
+        let (width, height) = self.current.mouse_mark_size;
+        (0.0..=width).contains(&x) && (0.0..=height).contains(&y)
+    }
+
+    /// The handle under `point`, given where the hand is drawn
+    pub fn handle_at(&self, point: (f32, f32), hand: (f32, f32)) -> Option<Handle> {
+        let near = |handle: (f32, f32)| distance(point, handle) <= GRAB_RADIUS;
+        if near(self.rotate_handle()) {
+            Some(Handle::MouseRotate)
+        } else if near(self.resize_handle()) {
+            Some(Handle::MouseResize)
+        } else if near(self.current.anchor) {
+            Some(Handle::Anchor)
+        } else if near(hand) {
+            Some(Handle::Hand)
+        } else if self.on_mouse_pad(point) {
+            Some(Handle::MousePad)
+        } else {
+            None
+        }
+    }
+
+    /// Starts dragging whatever is under `point`, returns whether there was anything
+    pub fn press(&mut self, point: (f32, f32), hand: (f32, f32)) -> bool {
+        self.drag = self.handle_at(point, hand).map(|handle| Drag {
+            handle,
+            from: point,
+            start: self.current,
+        });
+        self.drag.is_some()
+    }
+
+    /// Moves the dragged handle to `point`, returns whether the calibration changed
+    pub fn drag_to(&mut self, point: (f32, f32)) -> bool {
+        let drag = match self.drag {
+            Some(drag) => drag,
+            None => return false,
+        };
+        let start = drag.start;
+        let delta = (point.0 - drag.from.0, point.1 - drag.from.1);
+        let mut next = start;
+        match drag.handle {
+            Handle::Anchor => next.anchor = (start.anchor.0 + delta.0, start.anchor.1 + delta.1),
+            // The arm hangs from the offset, so moving it the other way moves the arm along
+            Handle::Hand => {
+                next.arm_offset = (start.arm_offset.0 - delta.0, start.arm_offset.1 - delta.1)
+            }
+            Handle::MousePad => {
+                let (x, y) = start.mouse_mark_position;
+                next.mouse_mark_position = (x + delta.0, y + delta.1);
+            }
+            Handle::MouseResize => {
+                if let Some(inverse) = start.mouse_mark_transform().inverse() {
+                    let (width, height) = inverse.apply(point);
+                    next.mouse_mark_size = (width.max(MIN_PAD_SIZE), height.max(MIN_PAD_SIZE));
+                }
+            }
+            Handle::MouseRotate => {
+                let center = start.mouse_mark_position;
+                next.mouse_mark_rotation =
+                    start.mouse_mark_rotation + angle(center, point) - angle(center, drag.from);
+            }
+        }
+        let changed = next != self.current;
+        self.current = next;
+        changed
+    }
+
+    /// Ends a drag, making it one step to undo
+    pub fn release(&mut self) {
+        if let Some(drag) = self.drag.take() {
+            if drag.start != self.current {
+                self.undo.push(drag.start);
+                self.redo.clear();
+            }
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(previous) => {
+                self.redo.push(self.current);
+                self.current = previous;
+                self.drag = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(next) => {
+                self.undo.push(self.current);
+                self.current = next;
+                self.drag = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draws the resize and rotate handles, the other handles are the debug marks
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        let (x, y) = self.resize_handle();
+        let mut resize = Shape::rectangle(Vector2f::new(HANDLE_SIZE, HANDLE_SIZE));
+        resize.origin = Vector2f::new(HANDLE_SIZE / 2.0, HANDLE_SIZE / 2.0);
+        resize.position = Vector2f::new(x, y);
+        resize.rotation = self.current.mouse_mark_rotation;
+        resize.fill = Color::YELLOW;
+        renderer.draw_shape(&resize);
+
+        let (x, y) = self.rotate_handle();
+        let radius = HANDLE_SIZE / 2.0;
+        let mut rotate = Shape::circle(radius);
+        rotate.origin = Vector2f::new(radius, radius);
+        rotate.position = Vector2f::new(x, y);
+        rotate.fill = Color::TRANSPARENT;
+        rotate.outline = Color::YELLOW;
+        rotate.outline_thickness = 2.0;
+        renderer.draw_shape(&rotate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sfml::system::Vector2;
+
+    fn editor() -> CalibrationEditor {
+        let mut config = Config::default();
+        config.anchors.anchor = Vector2::new(100.into(), 100.into());
+        config.mouse_mark.position = Vector2::new(200.into(), 200.into());
+        config.mouse_mark.size = Vector2::new(100.into(), 50.into());
+        config.mouse_mark.rotation = 0.into();
+        CalibrationEditor::new(&config)
+    }
+
+    const FAR_HAND: (f32, f32) = (-100.0, -100.0);
+
+    #[test]
+    fn test_handle_at() {
+        let editor = editor();
+        assert_eq!(Some(Handle::Anchor), editor.handle_at((103.0, 98.0), FAR_HAND));
+        assert_eq!(Some(Handle::Hand), editor.handle_at((10.0, 10.0), (12.0, 12.0)));
+        assert_eq!(Some(Handle::MousePad), editor.handle_at((250.0, 220.0), FAR_HAND));
+        assert_eq!(Some(Handle::MouseResize), editor.handle_at((300.0, 250.0), FAR_HAND));
+        assert_eq!(Some(Handle::MouseRotate), editor.handle_at((250.0, 176.0), FAR_HAND));
+        assert_eq!(None, editor.handle_at((0.0, 400.0), FAR_HAND));
+    }
+
+    #[test]
+    fn test_drag_anchor_then_undo_and_redo() {
+        let mut editor = editor();
+        assert!(editor.press((100.0, 100.0), FAR_HAND));
+        assert!(editor.drag_to((110.0, 95.0)));
+        assert!(editor.drag_to((120.0, 90.0)));
+        editor.release();
+        assert_eq!((120.0, 90.0), editor.current().anchor);
+
+        assert!(editor.undo());
+        assert_eq!((100.0, 100.0), editor.current().anchor);
+        assert!(!editor.undo());
+        assert!(editor.redo());
+        assert_eq!((120.0, 90.0), editor.current().anchor);
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn test_resize_follows_rotation() {
+        let mut editor = editor();
+        editor.current.mouse_mark_rotation = 90.0;
+        // Rotated a quarter turn clockwise, the pad's bottom right corner is down and to the left
+        let corner = editor.resize_handle();
+        assert!((corner.0 - 150.0).abs() < 1e-3 && (corner.1 - 300.0).abs() < 1e-3);
+        assert!(editor.press(corner, FAR_HAND));
+        editor.drag_to((140.0, 320.0));
+        let (width, height) = editor.current().mouse_mark_size;
+        assert!((width - 120.0).abs() < 1e-3 && (height - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_rotate_around_pad_corner() {
+        let mut editor = editor();
+        let handle = editor.rotate_handle();
+        assert!(editor.press(handle, FAR_HAND));
+        // A quarter turn clockwise around the top left corner
+        let turned = (200.0 + (200.0 - handle.1), 200.0 + (handle.0 - 200.0));
+        editor.drag_to(turned);
+        assert!((editor.current().mouse_mark_rotation - 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_new_drag_clears_redo() {
+        let mut editor = editor();
+        editor.press((100.0, 100.0), FAR_HAND);
+        editor.drag_to((110.0, 100.0));
+        editor.release();
+        editor.undo();
+        editor.press((100.0, 100.0), FAR_HAND);
+        editor.drag_to((100.0, 110.0));
+        editor.release();
+        assert!(!editor.redo());
+        assert!(editor.undo());
+        assert_eq!((100.0, 100.0), editor.current().anchor);
+    }
+}
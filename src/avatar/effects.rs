@@ -0,0 +1,427 @@
+use image::imageops;
+use image::{Rgba, RgbaImage};
+
+use super::DecodedFrame;
+use crate::config::{Color, Outline, PartSettings, Recolor, Shadow};
+use crate::Config;
+
+/// Transparent pixels added around an image to make room for its effects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Margin {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+/// Recoloring, outline and shadow baked into a part's pixels when it is decoded
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Effects {
+    recolor: Option<Recolor>,
+    outline: Option<Outline>,
+    shadow: Option<Shadow>,
+}
+
+impl Effects {
+    /// Effects for the part named `name`, with the active variant's recoloring
+    pub fn for_part(config: &Config, name: &str) -> Self {
+        Self {
+            recolor: config.part_recolor(name),
+            ..Self::from_settings(&config.part_settings(name))
+        }
+    }
+
+    pub fn from_settings(settings: &PartSettings) -> Self {
+        Self {
+            recolor: None,
+            outline: settings.outline.clone().filter(|outline| outline.width > 0),
+            shadow: settings
+                .shadow
+                .clone()
+                .filter(|shadow| shadow.opacity > 0.0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recolor.is_none() && self.outline.is_none() && self.shadow.is_none()
+    }
+
+    /// Identifies the processing applied, so differently processed copies of a file are cached apart
+    pub fn fingerprint(&self) -> String {
+        if self.is_empty() {
+            String::new()
+        } else {
+            format!("{:?}", self)
+        }
+    }
+
+    pub fn margin(&self) -> Margin {
+        let width = self.outline.as_ref().map_or(0, |outline| outline.width);
+        let mut margin = Margin {
+            left: width,
+            top: width,
+            right: width,
+            bottom: width,
+        };
+        if let Some(shadow) = &self.shadow {
+            let (dx, dy) = shadow_offset(shadow);
+            let spread = blur_extent(shadow.blur) as i64;
+            margin.left += (spread - dx).max(0) as u32;
+            margin.right += (spread + dx).max(0) as u32;
+            margin.top += (spread - dy).max(0) as u32;
+            margin.bottom += (spread + dy).max(0) as u32;
+        }
+        margin
+    }
+
+    pub fn apply(&self, frames: Vec<DecodedFrame>) -> (Vec<DecodedFrame>, Margin) {
+        if self.is_empty() {
+            return (frames, Margin::default());
+        }
+        let margin = self.margin();
+        let frames = frames
+            .into_iter()
+            .map(|frame| DecodedFrame {
+                image: self.apply_image(&frame.image, margin),
+                delay: frame.delay,
+            })
+            .collect();
+        (frames, margin)
+    }
+
+    fn apply_image(&self, image: &RgbaImage, margin: Margin) -> RgbaImage {
+        let mut result = pad(image, margin);
+        if let Some(recolor) = &self.recolor {
+            for pixel in result.pixels_mut() {
+                *pixel = recolor_pixel(*pixel, recolor);
+            }
+        }
+        if let Some(outline) = &self.outline {
+            result = draw_outline(&result, outline);
+        }
+        if let Some(shadow) = &self.shadow {
+            result = draw_shadow(&result, shadow);
+        }
+        result
+    }
+}
+
+fn recolor_pixel(pixel: Rgba<u8>, recolor: &Recolor) -> Rgba<u8> {
+    let Rgba([red, green, blue, alpha]) = pixel;
+    if alpha == 0 {
+        return pixel;
+    }
+    let mapped = recolor
+        .palette
+        .iter()
+        .find(|entry| (entry.from.red, entry.from.green, entry.from.blue) == (red, green, blue));
+    if let Some(entry) = mapped {
+        let alpha = alpha as u32 * entry.to.alpha as u32 / 255;
+        return Rgba([entry.to.red, entry.to.green, entry.to.blue, alpha as u8]);
+    }
+    let [mut red, mut green, mut blue] = if recolor.hue_shift != 0.0 {
+        shift_hue([red, green, blue], recolor.hue_shift)
+    } else {
+        [red, green, blue]
+    };
+    let mut alpha = alpha;
+    if let Some(tint) = &recolor.tint {
+        let multiply = |channel: u8, by: u8| (channel as u32 * by as u32 / 255) as u8;
+        red = multiply(red, tint.red);
+        green = multiply(green, tint.green);
+        blue = multiply(blue, tint.blue);
+        alpha = multiply(alpha, tint.alpha);
+    }
+    Rgba([red, green, blue, alpha])
+}
+
+/// Rotates the hue of a color by `degrees`, keeping its saturation and value
+fn shift_hue(rgb: [u8; 3], degrees: f32) -> [u8; 3] {
+    let [red, green, blue] = rgb.map(|channel| channel as f32 / 255.0);
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let chroma = max - min;
+    if chroma == 0.0 {
+        return rgb;
+    }
+    let hue = if max == red {
+        ((green - blue) / chroma).rem_euclid(6.0)
+    } else if max == green {
+        (blue - red) / chroma + 2.0
+    } else {
+        (red - green) / chroma + 4.0
+    };
+    let hue = (hue + degrees / 60.0).rem_euclid(6.0);
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (red, green, blue) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    [red, green, blue].map(|channel| ((channel + min) * 255.0).round() as u8)
+}
+
+fn shadow_offset(shadow: &Shadow) -> (i64, i64) {
+    let x: f32 = shadow.offset.x.into();
+    let y: f32 = shadow.offset.y.into();
+    (x.round() as i64, y.round() as i64)
+}
+
+/// How far a gaussian blur visibly spreads a shape
+fn blur_extent(sigma: f32) -> u32 {
+    (sigma.max(0.0) * 3.0).ceil() as u32
+}
+
+fn pad(image: &RgbaImage, margin: Margin) -> RgbaImage {
+    let mut padded = RgbaImage::new(
+        image.width() + margin.left + margin.right,
+        image.height() + margin.top + margin.bottom,
+    );
+    imageops::replace(&mut padded, image, margin.left as i64, margin.top as i64);
+    padded
+}
+
+/// Image filled with `color`, taking its shape from the alpha of `image`
+fn silhouette(image: &RgbaImage, color: &Color, alpha: impl Fn(u32, u32) -> f32) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let coverage = alpha(x, y) * color.alpha as f32 / 255.0;
+        Rgba([
+            color.red,
+            color.green,
+            color.blue,
+            (coverage * 255.0).round() as u8,
+        ])
+    })
+}
+
+fn draw_outline(image: &RgbaImage, outline: &Outline) -> RgbaImage {
+    let radius = outline.width as f32 + 0.5;
+    let reach = outline.width as i64 + 1;
+    // Disk of neighbours with their anti-aliased weight
+    let kernel: Vec<(i64, i64, f32)> = (-reach..=reach)
+        .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            (
+                dx,
+                dy,
+                (radius - ((dx * dx + dy * dy) as f32).sqrt()).clamp(0.0, 1.0),
+            )
+        })
+        .filter(|(_, _, weight)| *weight > 0.0)
+        .collect();
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let mut result = silhouette(image, &outline.color, |x, y| {
+        kernel
+            .iter()
+            .map(|(dx, dy, weight)| {
+                let (sx, sy) = (x as i64 + dx, y as i64 + dy);
+                if sx < 0 || sy < 0 || sx >= width || sy >= height {
+                    0.0
+                } else {
+                    image.get_pixel(sx as u32, sy as u32)[3] as f32 / 255.0 * weight
+                }
+            })
+            .fold(0.0, f32::max)
+    });
+    imageops::overlay(&mut result, image, 0, 0);
+    result
+}
+
+fn draw_shadow(image: &RgbaImage, shadow: &Shadow) -> RgbaImage {
+    let opacity = shadow.opacity.clamp(0.0, 1.0);
+    let mut casted = silhouette(image, &shadow.color, |x, y| {
+        image.get_pixel(x, y)[3] as f32 / 255.0 * opacity
+    });
+    if shadow.blur > 0.0 {
+        casted = imageops::blur(&casted, shadow.blur);
+    }
+    let (dx, dy) = shadow_offset(shadow);
+    let mut result = RgbaImage::new(image.width(), image.height());
+    imageops::overlay(&mut result, &casted, dx, dy);
+    imageops::overlay(&mut result, image, 0, 0);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PaletteEntry, Variant};
+    use sfml::system::Vector2;
+
+    fn dot() -> RgbaImage {
+        let mut image = RgbaImage::new(1, 1);
+        image.put_pixel(0, 0, Rgba([10, 20, 30, 255]));
+        image
+    }
+
+    fn outline(width: u32) -> Outline {
+        Outline {
+            color: Color {
+                red: 255,
+                green: 255,
+                blue: 255,
+                alpha: 255,
+            },
+            width,
+        }
+    }
+
+    fn shadow(x: isize, y: isize, blur: f32) -> Shadow {
+        Shadow {
+            color: Color::default(),
+            offset: Vector2::new(x.into(), y.into()),
+            blur,
+            opacity: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_no_effects_keep_frames() {
+        let effects = Effects::default();
+        let frames = vec![DecodedFrame {
+            image: dot(),
+            delay: Default::default(),
+        }];
+        let (frames, margin) = effects.apply(frames);
+        assert_eq!(Margin::default(), margin);
+        assert_eq!(dot(), frames[0].image);
+        assert_eq!("", effects.fingerprint());
+    }
+
+    #[test]
+    fn test_margin() {
+        let effects = Effects {
+            recolor: None,
+            outline: Some(outline(2)),
+            shadow: Some(shadow(4, -1, 1.0)),
+        };
+        let margin = Margin {
+            left: 2,
+            top: 6,
+            right: 9,
+            bottom: 4,
+        };
+        assert_eq!(margin, effects.margin());
+    }
+
+    #[test]
+    fn test_outline_surrounds_image() {
+        let effects = Effects {
+            recolor: None,
+            outline: Some(outline(2)),
+            shadow: None,
+        };
+        let (frames, margin) = effects.apply(vec![DecodedFrame {
+            image: dot(),
+            delay: Default::default(),
+        }]);
+        let image = &frames[0].image;
+        assert_eq!((5, 5), image.dimensions());
+        assert_eq!(
+            &Rgba([10, 20, 30, 255]),
+            image.get_pixel(margin.left, margin.top)
+        );
+        assert_eq!(&Rgba([255, 255, 255, 255]), image.get_pixel(1, 2));
+        assert_eq!(&Rgba([255, 255, 255, 255]), image.get_pixel(2, 3));
+        // Anti-aliased edge of the outline
+        assert_eq!(128, image.get_pixel(0, 2)[3]);
+        assert_eq!(0, image.get_pixel(0, 0)[3]);
+    }
+
+    #[test]
+    fn test_hard_shadow_is_offset() {
+        let effects = Effects {
+            recolor: None,
+            outline: None,
+            shadow: Some(shadow(2, 1, 0.0)),
+        };
+        let (frames, margin) = effects.apply(vec![DecodedFrame {
+            image: dot(),
+            delay: Default::default(),
+        }]);
+        let image = &frames[0].image;
+        assert_eq!((3, 2), image.dimensions());
+        assert_eq!((0, 0), (margin.left, margin.top));
+        assert_eq!(&Rgba([0, 0, 0, 255]), image.get_pixel(2, 1));
+        assert_eq!(0, image.get_pixel(1, 0)[3]);
+    }
+
+    #[test]
+    fn test_for_part_uses_active_variant() {
+        let mut config = Config::default();
+        let mut variant = Variant::default();
+        variant.parts.insert(
+            "avatar".to_string(),
+            Recolor {
+                hue_shift: 90.0,
+                ..Default::default()
+            },
+        );
+        config.variants.insert("blue".to_string(), variant);
+        assert!(Effects::for_part(&config, "avatar").is_empty());
+
+        config.variant = Some("blue".to_string());
+        let effects = Effects::for_part(&config, "avatar");
+        assert!(!effects.is_empty());
+        assert_ne!("", effects.fingerprint());
+        assert!(Effects::for_part(&config, "left_hand").is_empty());
+    }
+
+    #[test]
+    fn test_palette_replaces_exact_colors() {
+        let recolor = Recolor {
+            palette: vec![PaletteEntry {
+                from: Color {
+                    red: 10,
+                    green: 20,
+                    blue: 30,
+                    alpha: 255,
+                },
+                to: Color {
+                    red: 200,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                },
+            }],
+            hue_shift: 180.0,
+            tint: None,
+        };
+        assert_eq!(
+            Rgba([200, 0, 0, 128]),
+            recolor_pixel(Rgba([10, 20, 30, 128]), &recolor)
+        );
+        // Colors outside the palette get the hue shift instead
+        assert_eq!(
+            Rgba([0, 255, 255, 255]),
+            recolor_pixel(Rgba([255, 0, 0, 255]), &recolor)
+        );
+    }
+
+    #[test]
+    fn test_shift_hue() {
+        assert_eq!([0, 255, 0], shift_hue([255, 0, 0], 120.0));
+        assert_eq!([0, 0, 255], shift_hue([255, 0, 0], -120.0));
+        assert_eq!([128, 128, 128], shift_hue([128, 128, 128], 90.0));
+    }
+
+    #[test]
+    fn test_tint_multiplies() {
+        let recolor = Recolor {
+            tint: Some(Color {
+                red: 255,
+                green: 0,
+                blue: 255,
+                alpha: 255,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            Rgba([100, 0, 50, 200]),
+            recolor_pixel(Rgba([100, 100, 50, 200]), &recolor)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::WindowFinderError;
 use core::result;
 use sfml::ResourceLoadError;
+use std::io;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,6 +12,18 @@ pub enum SfmlError {
     PathConversion,
     #[error("window finder error")]
     WindowFinder(#[from] WindowFinderError),
+    #[error("reading image file")]
+    File(#[from] io::Error),
+    #[error("decoding image")]
+    Decode(#[from] image::ImageError),
+    #[error("creating image from pixels")]
+    ImageCreate,
+    #[error("image has no frames")]
+    EmptyImage,
+    #[error("loading font")]
+    FontLoad,
+    #[error("creating render texture")]
+    RenderTexture,
 }
 
 pub type SfmlResult<T> = result::Result<T, SfmlError>;
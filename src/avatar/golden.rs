@@ -0,0 +1,219 @@
+// Each case drives the view models into a scripted input state, draws the fixture skin in
+// `tests/fixtures/skin` with the software renderer and compares the frame with
+// `tests/golden/<case>.png`. A failing case writes what it drew and a diff image into
+// `target/golden`. `make bless` (`PTUBER_BLESS=1 cargo test golden`) writes the golden
+// images, a case without one fails until it is blessed.
+
+use device_query::Keycode;
+use image::{Rgba, RgbaImage};
+use log::info;
+use sfml::system::Vector2f;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{software_avatar, SceneCapture};
+use crate::models::DeviceButton;
+use crate::render::straight_alpha;
+use crate::user_input::{DeviceEvent, KeyboardEvent};
+use crate::view_models::{
+    DeviceViewModelImpl, KeyboardState, KeyboardViewModelImpl, MouseButtonState,
+};
+use crate::Config;
+
+/// Channel differences up to this much are rounding, not a change
+const CHANNEL_TOLERANCE: u8 = 2;
+/// Share of the pixels allowed to differ by more than `CHANNEL_TOLERANCE`
+const PIXEL_TOLERANCE: f64 = 0.001;
+const BLESS_VAR: &str = "PTUBER_BLESS";
+
+/// Input the rig is drawn with
+struct Script {
+    keyboard: KeyboardState,
+    buttons: MouseButtonState,
+    /// Normalized cursor position
+    cursor: (f32, f32),
+}
+
+struct Comparison {
+    differing: usize,
+    total: usize,
+    /// The expected frame faded, with differing pixels in red
+    diff: RgbaImage,
+}
+
+impl Comparison {
+    fn passes(&self) -> bool {
+        self.differing as f64 <= self.total as f64 * PIXEL_TOLERANCE
+    }
+}
+
+fn manifest_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Comparison {
+    let total = (expected.width() * expected.height()) as usize;
+    if actual.dimensions() != expected.dimensions() {
+        let (width, height) = expected.dimensions();
+        let diff = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]));
+        return Comparison { differing: total, total, diff };
+    }
+    let mut differing = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let changed = a.0.iter().zip(e.0).any(|(a, e)| a.abs_diff(e) > CHANNEL_TOLERANCE);
+        *d = if changed {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3) as u8;
+            Rgba([gray, gray, gray, 64])
+        };
+    }
+    Comparison { differing, total, diff }
+}
+
+/// Presses keys and buttons until the view models report the scripted state
+fn apply_script(script: &Script, keyboard: &KeyboardViewModelImpl, mouse: &DeviceViewModelImpl) {
+    // One key held reads as the right hand, two as the left
+    let keys: &[Keycode] = match script.keyboard {
+        KeyboardState::Up => &[],
+        KeyboardState::Right => &[Keycode::J],
+        KeyboardState::Left => &[Keycode::A, Keycode::S],
+    };
+    for key in keys {
+        keyboard.handle_event(&KeyboardEvent::KeyPressed(*key));
+    }
+    let buttons: &[DeviceButton] = match script.buttons {
+        MouseButtonState::None => &[],
+        MouseButtonState::Left => &[DeviceButton::Left],
+        MouseButtonState::Right => &[DeviceButton::Right],
+        MouseButtonState::Both => &[DeviceButton::Left, DeviceButton::Right],
+    };
+    for button in buttons {
+        mouse.handle_event(&DeviceEvent::ButtonPressed(*button));
+    }
+    let (x, y) = script.cursor;
+    mouse.handle_event(&DeviceEvent::MouseMoved(Vector2f::new(x, y)));
+    assert_eq!(script.keyboard, keyboard.keyboard_state());
+    assert_eq!(script.buttons, mouse.button_state());
+}
+
+fn render(script: &Script) -> RgbaImage {
+    let mut config = Config::default();
+    config.images_path = manifest_path("tests/fixtures/skin");
+    let background = config.background.clone().into();
+    let mut avatar = software_avatar(config).expect("fixture skin loads");
+    let keyboard = KeyboardViewModelImpl::new();
+    let mouse = DeviceViewModelImpl::new();
+    apply_script(script, &keyboard, &mouse);
+    let mut image = SceneCapture::default()
+        .draw(&mut avatar, &keyboard, &mouse, background)
+        .expect("scene draws");
+    straight_alpha(&mut image);
+    image
+}
+
+fn check_golden(name: &str, script: Script) {
+    let actual = render(&script);
+    let golden = manifest_path("tests/golden").join(format!("{}.png", name));
+    if env::var_os(BLESS_VAR).is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save(&golden).unwrap();
+        info!("Blessed {}", golden.display());
+        return;
+    }
+    assert!(
+        golden.exists(),
+        "{} has no golden image at {}, run `make bless` to record it",
+        name,
+        golden.display()
+    );
+
+    let expected = image::open(&golden).unwrap().into_rgba8();
+    let comparison = compare(&actual, &expected);
+    if !comparison.passes() {
+        let out = manifest_path("target/golden");
+        fs::create_dir_all(&out).unwrap();
+        let actual_path = out.join(format!("{}.actual.png", name));
+        let diff_path = out.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        comparison.diff.save(&diff_path).unwrap();
+        panic!(
+            "{} differs from {} in {} of {} pixels, see {} and {}. \
+             Run `make bless` if the change is intended",
+            name,
+            golden.display(),
+            comparison.differing,
+            comparison.total,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn test_compare_tolerance() {
+    let expected = RgbaImage::from_pixel(20, 20, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([102, 98, 100, 255]));
+    assert_eq!(0, compare(&actual, &expected).differing);
+
+    actual.put_pixel(1, 0, Rgba([200, 100, 100, 255]));
+    let comparison = compare(&actual, &expected);
+    assert_eq!(1, comparison.differing);
+    assert_eq!(Rgba([255, 0, 0, 255]), *comparison.diff.get_pixel(1, 0));
+    assert!(!comparison.passes());
+
+    let smaller = RgbaImage::new(10, 10);
+    assert_eq!(400, compare(&smaller, &expected).differing);
+}
+
+#[test]
+fn test_golden_idle() {
+    check_golden(
+        "idle",
+        Script {
+            keyboard: KeyboardState::Up,
+            buttons: MouseButtonState::None,
+            cursor: (0.5, 0.5),
+        },
+    );
+}
+
+#[test]
+fn test_golden_left_hand_left_click_top_left() {
+    check_golden(
+        "left_hand_left_click_top_left",
+        Script {
+            keyboard: KeyboardState::Left,
+            buttons: MouseButtonState::Left,
+            cursor: (0.0, 0.0),
+        },
+    );
+}
+
+#[test]
+fn test_golden_right_hand_right_click_bottom_right() {
+    check_golden(
+        "right_hand_right_click_bottom_right",
+        Script {
+            keyboard: KeyboardState::Right,
+            buttons: MouseButtonState::Right,
+            cursor: (1.0, 1.0),
+        },
+    );
+}
+
+#[test]
+fn test_golden_both_buttons_far_left() {
+    check_golden(
+        "both_buttons_far_left",
+        Script {
+            keyboard: KeyboardState::Up,
+            buttons: MouseButtonState::Both,
+            cursor: (0.1, 0.9),
+        },
+    );
+}
@@ -0,0 +1,205 @@
+use sfml::graphics::{
+    Color, Font, RectangleShape, RenderTarget, RenderWindow, Shape, Text, Transformable,
+};
+use rust_embed::RustEmbed;
+use sfml::system::Vector2f;
+use sfml::SfBox;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use super::{SfmlError, SfmlResult};
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::recent_log_lines;
+
+const HUD_FONT_PATH: &str = "DejaVuSansMono.ttf";
+const FONT_SIZE: u32 = 12;
+const PADDING: f32 = 6.0;
+/// Metrics are averaged over this much time
+const WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+#[include = "DejaVuSansMono.ttf"]
+struct Assets;
+
+/// Durations measured over the last second
+#[derive(Debug, Clone, Default)]
+pub struct Samples {
+    samples: VecDeque<(Instant, Duration)>,
+}
+
+impl Samples {
+    pub fn push(&mut self, now: Instant, duration: Duration) {
+        while let Some((at, _)) = self.samples.front() {
+            if now.duration_since(*at) < WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, duration));
+    }
+
+    /// Samples per second
+    pub fn rate(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn average(&self) -> Duration {
+        let total: Duration = self.samples.iter().map(|(_, duration)| *duration).sum();
+        total / self.samples.len().max(1) as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples
+            .iter()
+            .map(|(_, duration)| *duration)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Live metrics drawn over the scene, toggled with a hotkey
+#[derive(Debug)]
+pub struct Hud {
+    font: SfBox<Font>,
+    visible: bool,
+    frames: Samples,
+    polls: Samples,
+}
+
+impl Hud {
+    pub fn new() -> SfmlResult<Self> {
+        let font_data = Assets::get(HUD_FONT_PATH).ok_or(SfmlError::FontLoad)?.data;
+        let font_data: &'static [u8] = match font_data {
+            Cow::Borrowed(data) => data,
+            // Debug builds read assets from disk, and the font reads from its data while in use
+            Cow::Owned(data) => Box::leak(data.into_boxed_slice()),
+        };
+        let font = Font::from_memory_static(font_data).ok_or(SfmlError::FontLoad)?;
+        Ok(Self {
+            font,
+            visible: false,
+            frames: Default::default(),
+            polls: Default::default(),
+        })
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// A frame that took `duration` to draw was shown
+    pub fn record_frame(&mut self, now: Instant, duration: Duration) {
+        self.frames.push(now, duration);
+    }
+
+    /// Reading input took `duration`
+    pub fn record_poll(&mut self, now: Instant, duration: Duration) {
+        self.polls.push(now, duration);
+    }
+
+    fn text(
+        &self,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        hand: Vector2f,
+    ) -> String {
+        let mut text = String::new();
+        let cursor = mouse.position();
+        let keys: Vec<String> = keyboard
+            .keys_pressed()
+            .iter()
+            .map(|key| format!("{:?}", key))
+            .chain(mouse.buttons_pressed().iter().map(|b| format!("Mouse({:?})", b)))
+            .collect();
+        // Writing to a String can't fail
+        let _ = writeln!(
+            text,
+            "FPS {:>3}  frame {:.2} ms (max {:.2})",
+            self.frames.rate(),
+            millis(self.frames.average()),
+            millis(self.frames.max())
+        );
+        let _ = writeln!(
+            text,
+            "Input {:.2} ms (max {:.2}) at {}/s",
+            millis(self.polls.average()),
+            millis(self.polls.max()),
+            self.polls.rate()
+        );
+        let _ = writeln!(text, "Device {:?}", mouse.device_type());
+        let _ = writeln!(text, "Pressed {}", keys.join(" "));
+        let _ = writeln!(text, "Cursor {:.3}, {:.3}", cursor.x, cursor.y);
+        let _ = writeln!(text, "Hand {:.1}, {:.1}", hand.x, hand.y);
+        for line in recent_log_lines() {
+            let _ = writeln!(text, "{}", line);
+        }
+        text
+    }
+
+    /// Draws the metrics in the top left corner of the window, outside the letterboxed scene
+    pub fn draw(
+        &self,
+        window: &mut RenderWindow,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        hand: Vector2f,
+    ) {
+        let scene_view = window.view().to_owned();
+        let view = window.default_view().to_owned();
+        window.set_view(&view);
+
+        let string = self.text(keyboard, mouse, hand);
+        let mut text = Text::new(string.trim_end(), &self.font, FONT_SIZE);
+        text.set_fill_color(Color::WHITE);
+        text.set_position(Vector2f::new(PADDING, PADDING));
+        let bounds = text.global_bounds();
+        let mut panel = RectangleShape::with_size(Vector2f::new(
+            bounds.width + PADDING * 2.0,
+            bounds.top + bounds.height + PADDING,
+        ));
+        panel.set_fill_color(Color::rgba(0, 0, 0, 160));
+        window.draw(&panel);
+        window.draw(&text);
+
+        window.set_view(&scene_view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_over_last_second() {
+        let start = Instant::now();
+        let mut samples = Samples::default();
+        samples.push(start, Duration::from_millis(9));
+        samples.push(start + Duration::from_millis(500), Duration::from_millis(1));
+        samples.push(start + Duration::from_millis(900), Duration::from_millis(2));
+        assert_eq!(3, samples.rate());
+        assert_eq!(Duration::from_millis(4), samples.average());
+
+        samples.push(start + Duration::from_millis(1200), Duration::from_millis(3));
+        assert_eq!(3, samples.rate());
+        assert_eq!(Duration::from_millis(2), samples.average());
+        assert_eq!(Duration::from_millis(3), samples.max());
+    }
+
+    #[test]
+    fn test_empty_samples() {
+        let samples = Samples::default();
+        assert_eq!(0, samples.rate());
+        assert_eq!(Duration::ZERO, samples.average());
+    }
+}
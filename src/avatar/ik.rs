@@ -0,0 +1,140 @@
+use sfml::system::Vector2f;
+
+use crate::config::{BendDirection, IkArmSettings};
+
+const TO_DEGREE: f32 = 180.0 / std::f32::consts::PI;
+
+/// Joint positions of a two-segment arm, on the skin canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ArmPose {
+    pub shoulder: Vector2f,
+    pub elbow: Vector2f,
+    pub hand: Vector2f,
+}
+
+fn length(vector: Vector2f) -> f32 {
+    vector.x.hypot(vector.y)
+}
+
+/// Rotation, in degrees, of a segment image hanging straight down from `from` to point at `to`
+pub(crate) fn segment_rotation(from: Vector2f, to: Vector2f) -> f32 {
+    let direction = to - from;
+    direction.y.atan2(direction.x) * TO_DEGREE - 90.0
+}
+
+/// Places the elbow so the hand gets as close to `target` as the arm allows.
+///
+/// Past `max_reach` the arm stays stretched towards the target and the shoulder leans after
+/// it a little.
+pub(crate) fn solve_arm(shoulder: Vector2f, target: Vector2f, settings: &IkArmSettings) -> ArmPose {
+    let upper = settings.upper_length.max(0.0);
+    let forearm = settings.forearm_length.max(0.0);
+    let reach = (upper + forearm) * settings.max_reach.clamp(0.0, 1.0);
+    let closest = (upper - forearm).abs();
+
+    let offset = target - shoulder;
+    let distance = length(offset);
+    // Straight down when the target sits on the shoulder
+    let direction = if distance > f32::EPSILON {
+        offset / distance
+    } else {
+        Vector2f::new(0.0, 1.0)
+    };
+    let overshoot = (distance - reach).max(0.0);
+    let shift = (overshoot * settings.shoulder_shift)
+        .min(settings.max_shoulder_shift)
+        .max(0.0);
+    let shoulder = shoulder + direction * shift;
+    let distance = (distance - shift).clamp(closest, reach.max(closest));
+    let hand = shoulder + direction * distance;
+
+    // Law of cosines for the angle between the upper arm and the shoulder to hand line
+    let cos = if upper > 0.0 && distance > 0.0 {
+        (upper * upper + distance * distance - forearm * forearm) / (2.0 * upper * distance)
+    } else {
+        1.0
+    };
+    let angle = match settings.bend {
+        BendDirection::Clockwise => cos.clamp(-1.0, 1.0).acos(),
+        BendDirection::CounterClockwise => -cos.clamp(-1.0, 1.0).acos(),
+    };
+    let (sin, cos) = angle.sin_cos();
+    let upper_direction = Vector2f::new(
+        direction.x * cos - direction.y * sin,
+        direction.x * sin + direction.y * cos,
+    );
+    ArmPose {
+        shoulder,
+        elbow: shoulder + upper_direction * upper,
+        hand,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> IkArmSettings {
+        IkArmSettings {
+            upper_length: 100.0,
+            forearm_length: 100.0,
+            max_reach: 0.9,
+            shoulder_shift: 0.5,
+            max_shoulder_shift: 10.0,
+            ..Default::default()
+        }
+    }
+
+    fn assert_near(expected: Vector2f, actual: Vector2f) {
+        assert!(length(expected - actual) < 1e-3, "{:?} != {:?}", expected, actual);
+    }
+
+    #[test]
+    fn test_reachable_target_keeps_segment_lengths() {
+        let shoulder = Vector2f::new(0.0, 0.0);
+        let target = Vector2f::new(120.0, 50.0);
+        let pose = solve_arm(shoulder, target, &settings());
+        assert_near(shoulder, pose.shoulder);
+        assert_near(target, pose.hand);
+        assert!((length(pose.elbow - pose.shoulder) - 100.0).abs() < 1e-3);
+        assert!((length(pose.hand - pose.elbow) - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_bend_direction_picks_elbow_side() {
+        let shoulder = Vector2f::new(0.0, 0.0);
+        let target = Vector2f::new(0.0, 100.0);
+        let clockwise = solve_arm(shoulder, target, &settings());
+        // Turning the downward line clockwise on screen swings the elbow to the left
+        assert!(clockwise.elbow.x < 0.0, "{:?}", clockwise);
+
+        let counter = IkArmSettings {
+            bend: BendDirection::CounterClockwise,
+            ..settings()
+        };
+        let counter = solve_arm(shoulder, target, &counter);
+        assert_near(Vector2f::new(-clockwise.elbow.x, clockwise.elbow.y), counter.elbow);
+    }
+
+    #[test]
+    fn test_out_of_reach_shifts_shoulder() {
+        let shoulder = Vector2f::new(0.0, 0.0);
+        // 180 is the reach, 10 past it moves the shoulder by half of that
+        let pose = solve_arm(shoulder, Vector2f::new(190.0, 0.0), &settings());
+        assert_near(Vector2f::new(5.0, 0.0), pose.shoulder);
+        assert_near(Vector2f::new(185.0, 0.0), pose.hand);
+
+        // The shoulder moves no more than `max_shoulder_shift`
+        let pose = solve_arm(shoulder, Vector2f::new(400.0, 0.0), &settings());
+        assert_near(Vector2f::new(10.0, 0.0), pose.shoulder);
+        assert_near(Vector2f::new(190.0, 0.0), pose.hand);
+    }
+
+    #[test]
+    fn test_segment_rotation_hangs_down_at_zero() {
+        let origin = Vector2f::new(0.0, 0.0);
+        assert!(segment_rotation(origin, Vector2f::new(0.0, 10.0)).abs() < 1e-4);
+        assert!((segment_rotation(origin, Vector2f::new(-10.0, 0.0)) - 90.0).abs() < 1e-4);
+        assert!((segment_rotation(origin, Vector2f::new(10.0, 0.0)) + 90.0).abs() < 1e-4);
+    }
+}
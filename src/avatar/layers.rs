@@ -0,0 +1,49 @@
+use sfml::graphics::Color;
+use sfml::system::Vector2f;
+
+use crate::config::Layer;
+use crate::render::{Affine, DrawStates, Renderer, SpriteDraw};
+
+/// How everything in one layer is moved, faded and blended onto the scene
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LayerStyle {
+    states: DrawStates,
+    color: Color,
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        Self {
+            states: Default::default(),
+            color: Color::WHITE,
+        }
+    }
+}
+
+impl LayerStyle {
+    pub fn new(layer: &Layer) -> Self {
+        let offset: Vector2f = layer.offset.into_other();
+        let scale: Vector2f = layer.scale.into_other();
+        let origin: Vector2f = layer.origin.into_other();
+        // Rotating and scaling around `origin`, then moving by `offset`
+        let transform = Affine::sprite(
+            (offset.x + origin.x, offset.y + origin.y),
+            (origin.x, origin.y),
+            (scale.x, scale.y),
+            layer.rotation,
+        );
+        let alpha = (layer.opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self {
+            states: DrawStates {
+                transform,
+                blend: layer.blend,
+            },
+            color: Color::rgba(255, 255, 255, alpha),
+        }
+    }
+
+    pub fn draw(&self, renderer: &mut dyn Renderer, mut sprite: SpriteDraw) {
+        sprite.color = self.color;
+        renderer.draw_sprite(&sprite, &self.states);
+    }
+}
@@ -0,0 +1,46 @@
+use sfml::graphics::{Color, RectangleShape, RenderTarget, RenderWindow, Shape, Transformable};
+use sfml::system::Vector2f;
+use sfml::window::Event;
+
+const BAR_HEIGHT: f32 = 16.0;
+const BAR_MARGIN: f32 = 48.0;
+
+/// Draws a progress bar for `done` out of `total` images and presents it immediately.
+///
+/// Window events that arrive meanwhile are added to `queued`, for the main loop to handle once
+/// loading is done.
+pub fn draw_loading_screen(
+    window: &mut RenderWindow,
+    queued: &mut Vec<Event>,
+    background: Color,
+    done: usize,
+    total: usize,
+) {
+    while let Some(event) = window.poll_event() {
+        queued.push(event);
+    }
+
+    let size = window.view().size();
+    let width = (size.x - BAR_MARGIN * 2.0).max(1.0);
+    let position = Vector2f::new(BAR_MARGIN, (size.y - BAR_HEIGHT) / 2.0);
+    let fraction = if total == 0 {
+        1.0
+    } else {
+        done as f32 / total as f32
+    };
+
+    let mut outline = RectangleShape::with_size(Vector2f::new(width, BAR_HEIGHT));
+    outline.set_position(position);
+    outline.set_fill_color(Color::TRANSPARENT);
+    outline.set_outline_color(Color::WHITE);
+    outline.set_outline_thickness(2.0);
+
+    let mut fill = RectangleShape::with_size(Vector2f::new(width * fraction, BAR_HEIGHT));
+    fill.set_position(position);
+    fill.set_fill_color(Color::WHITE);
+
+    window.clear(background);
+    window.draw(&fill);
+    window.draw(&outline);
+    window.display();
+}
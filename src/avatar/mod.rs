@@ -1,13 +1,53 @@
+mod animation;
 mod arms;
 mod avatar_impl;
+mod clips;
 mod device;
+mod editor;
+mod effects;
 mod errors;
+#[cfg(test)]
+mod golden;
+mod hud;
+mod ik;
+mod layers;
+mod loading;
+mod offline;
+mod pacing;
+mod smoothing;
+mod texture_store;
 mod textures;
+mod video;
+mod viewport;
 mod window;
+mod window_state;
 
+pub(crate) use self::animation::{decode_frames, DecodedFrame, Part, TextureFrames};
 pub(crate) use self::arms::Arms;
-pub(crate) use self::avatar_impl::Avatar;
+pub(crate) use self::avatar_impl::{Avatar, FrameState};
+pub(crate) use self::clips::{ClipAction, ClipRecorder};
 pub(crate) use self::device::Device;
-pub(crate) use self::textures::{ArmTextures, AvatarTextures, MouseTextures, TextureContainer};
+pub(crate) use self::editor::CalibrationEditor;
+pub(crate) use self::effects::{Effects, Margin};
+pub(crate) use self::hud::Hud;
+pub(crate) use self::ik::{segment_rotation, solve_arm, ArmPose};
+pub(crate) use self::layers::LayerStyle;
+pub(crate) use self::loading::draw_loading_screen;
+pub(crate) use self::offline::{offscreen_avatar, render_session};
+#[cfg(test)]
+pub(crate) use self::offline::software_avatar;
+pub(crate) use self::pacing::{Pacer, Redraw};
+pub(crate) use self::smoothing::HandSmoother;
+pub(crate) use self::texture_store::{TextureSource, TextureStore};
+pub(crate) use self::textures::{
+    part_name, skin_images, skin_textures, ArmTextures, AvatarTextures, MouseTextures,
+    TextureContainer,
+};
+pub(crate) use self::video::{
+    capture_outputs, outputs_wait, run_headless, SceneCapture, SceneOutput, StreamOutput,
+    VideoOutput,
+};
+pub(crate) use self::viewport::{apply_letterbox, letterbox, scene_size};
 pub(crate) use errors::{SfmlError, SfmlResult};
 pub(crate) use window::PtuberWindow;
+pub(crate) use window_state::WindowState;
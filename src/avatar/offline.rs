@@ -0,0 +1,101 @@
+use image::RgbaImage;
+use log::info;
+use sfml::graphics::{Color, Shader};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::{skin_textures, Avatar, SceneCapture, TextureStore};
+use crate::clock;
+use crate::errors::Result;
+use crate::render::{straight_alpha, RenderError, RenderResult};
+use crate::session::Session;
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::Config;
+
+/// Loads the skin for the software renderer, which needs no window
+pub fn software_avatar(config: Config) -> Result<Avatar> {
+    load_avatar(TextureStore::software(), config)
+}
+
+/// Loads the skin to be drawn without a window the way the window draws it: as textures
+/// when the config has shaders to run, for the software renderer otherwise
+pub fn offscreen_avatar(config: Config) -> Result<Avatar> {
+    if !config.shaders.iter().any(|pass| pass.enabled) {
+        return software_avatar(config);
+    }
+    if !Shader::is_available() {
+        return Err(RenderError::NoShaders.into());
+    }
+    load_avatar(TextureStore::new(), config)
+}
+
+fn load_avatar(mut store: TextureStore, config: Config) -> Result<Avatar> {
+    let images_path = config.images_path.clone();
+    store.set_smooth(!config.window.pixel_art);
+    store.preload(&images_path, &skin_textures(&config), |_, _| {})?;
+    Avatar::new(store, &images_path, config)
+}
+
+/// Plays `session` through the rig and writes one PNG per frame into `out_dir`.
+///
+/// The clock is stepped frame by frame, so animations and idle timers look the same as
+/// live however long a frame takes to draw. Frames are transparent unless `opaque`,
+/// in which case they get the background color. Returns how many frames were written.
+pub fn render_session(
+    config: Config,
+    session: &mut Session,
+    out_dir: &Path,
+    fps: u32,
+    opaque: bool,
+) -> Result<usize> {
+    let start = Instant::now();
+    clock::set_frozen(Some(start));
+    let rendered = render_frames(config, session, out_dir, fps, opaque, start);
+    clock::set_frozen(None);
+    rendered
+}
+
+fn render_frames(
+    mut config: Config,
+    session: &mut Session,
+    out_dir: &Path,
+    fps: u32,
+    opaque: bool,
+    start: Instant,
+) -> Result<usize> {
+    fs::create_dir_all(out_dir).map_err(RenderError::from)?;
+    let background = if opaque {
+        config.background.clone().into()
+    } else {
+        // Leaves out the built-in background like a transparent window does
+        config.window.transparent = true;
+        Color::TRANSPARENT
+    };
+
+    let mut avatar = offscreen_avatar(config)?;
+    let mut scene = SceneCapture::default();
+    let keyboard = KeyboardViewModelImpl::new();
+    let mouse = DeviceViewModelImpl::new();
+
+    let fps = fps.max(1);
+    let frames = (session.duration().as_secs_f64() * fps as f64).floor() as usize + 1;
+    info!("Rendering {} frames into {}", frames, out_dir.display());
+    for frame in 0..frames {
+        let time = Duration::from_secs_f64(frame as f64 / fps as f64);
+        clock::set_frozen(Some(start + time));
+        session.play_until(time, &keyboard, &mouse);
+
+        let mut image = scene.draw(&mut avatar, &keyboard, &mouse, background)?;
+        if !opaque {
+            straight_alpha(&mut image);
+        }
+        write_frame(&image, &out_dir.join(format!("frame_{:06}.png", frame)))?;
+    }
+    Ok(frames)
+}
+
+fn write_frame(image: &RgbaImage, path: &Path) -> RenderResult<()> {
+    image.save(path)?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use crate::config::RenderSettings;
+
+/// An unchanged scene is still redrawn this often, in case the window lost its contents
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Decides when input is read and when frames are drawn, each at its own rate
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    frame_interval: Duration,
+    poll_interval: Duration,
+    next_frame: Instant,
+    next_poll: Instant,
+}
+
+/// Moves `next` one interval on, without catching up on ticks that were missed
+fn advance(next: &mut Instant, interval: Duration, now: Instant) -> bool {
+    if now < *next {
+        return false;
+    }
+    *next += interval;
+    if *next <= now {
+        *next = now + interval;
+    }
+    true
+}
+
+impl Pacer {
+    pub fn new(settings: &RenderSettings, now: Instant) -> Self {
+        Self {
+            frame_interval: settings.frame_interval(),
+            poll_interval: settings.poll_interval(),
+            next_frame: now,
+            next_poll: now,
+        }
+    }
+
+    pub fn set_rates(&mut self, settings: &RenderSettings) {
+        self.frame_interval = settings.frame_interval();
+        self.poll_interval = settings.poll_interval();
+    }
+
+    pub fn poll_due(&mut self, now: Instant) -> bool {
+        advance(&mut self.next_poll, self.poll_interval, now)
+    }
+
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        advance(&mut self.next_frame, self.frame_interval, now)
+    }
+
+    /// How long to sleep before something is due
+    pub fn wait(&self, now: Instant) -> Duration {
+        self.next_frame
+            .min(self.next_poll)
+            .saturating_duration_since(now)
+    }
+}
+
+/// Remembers what the last drawn frame showed so unchanged frames can be skipped
+#[derive(Debug, Clone)]
+pub struct Redraw<T> {
+    last: Option<T>,
+    drawn_at: Option<Instant>,
+}
+
+impl<T> Default for Redraw<T> {
+    fn default() -> Self {
+        Self {
+            last: None,
+            drawn_at: None,
+        }
+    }
+}
+
+impl<T: PartialEq> Redraw<T> {
+    /// The next frame is drawn whatever it shows
+    pub fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// Whether a frame showing `state` has to be drawn, remembering it when it does
+    pub fn update(&mut self, state: T, now: Instant) -> bool {
+        let fresh = self
+            .drawn_at
+            .map_or(false, |drawn_at| now.duration_since(drawn_at) < REFRESH_INTERVAL);
+        if fresh && self.last.as_ref() == Some(&state) {
+            return false;
+        }
+        self.last = Some(state);
+        self.drawn_at = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(framerate: u32, poll_rate: u32) -> RenderSettings {
+        RenderSettings {
+            framerate,
+            poll_rate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_poll_and_frame_rates_are_separate() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(&settings(10, 100), start);
+        let (mut frames, mut polls) = (0, 0);
+        for ms in 0..1000 {
+            let now = start + Duration::from_millis(ms);
+            frames += pacer.frame_due(now) as u32;
+            polls += pacer.poll_due(now) as u32;
+        }
+        assert_eq!(10, frames);
+        assert_eq!(100, polls);
+    }
+
+    #[test]
+    fn test_missed_frames_are_not_caught_up() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(&settings(10, 10), start);
+        assert!(pacer.frame_due(start));
+        let late = start + Duration::from_secs(1);
+        assert!(pacer.frame_due(late));
+        assert!(!pacer.frame_due(late));
+        assert!(pacer.poll_due(late));
+        assert_eq!(Duration::from_millis(100), pacer.wait(late));
+    }
+
+    #[test]
+    fn test_redraw_skips_unchanged_frames() {
+        let start = Instant::now();
+        let mut redraw = Redraw::default();
+        assert!(redraw.update(1, start));
+        assert!(!redraw.update(1, start + Duration::from_millis(16)));
+        assert!(redraw.update(2, start + Duration::from_millis(32)));
+        redraw.invalidate();
+        assert!(redraw.update(2, start + Duration::from_millis(48)));
+    }
+
+    #[test]
+    fn test_redraw_refreshes_unchanged_frames() {
+        let start = Instant::now();
+        let mut redraw = Redraw::default();
+        assert!(redraw.update(1, start));
+        assert!(redraw.update(1, start + REFRESH_INTERVAL));
+    }
+}
@@ -0,0 +1,193 @@
+use sfml::system::Vector2f;
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use crate::config::{HandFilter, SmoothingSettings};
+
+/// Distance, in screen widths, under which a resting hand snaps onto the cursor
+const SETTLE_DISTANCE: f32 = 1e-4;
+/// Speed under which a hand that close counts as resting
+const SETTLE_SPEED: f32 = 1e-3;
+
+fn length(vector: Vector2f) -> f32 {
+    vector.x.hypot(vector.y)
+}
+
+/// Share of the way a first-order low-pass with `cutoff` Hz moves in `dt` seconds
+fn low_pass(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff.max(1e-3));
+    1.0 / (1.0 + tau / dt)
+}
+
+/// Filters the cursor position the hand follows and predicts it a little ahead
+#[derive(Debug, Clone)]
+pub(crate) struct HandSmoother {
+    settings: SmoothingSettings,
+    /// Filtered position and when it was computed, `None` until the first update
+    filtered: Option<(Vector2f, Instant)>,
+    /// Speed of the filtered position, the spring's own or an estimate
+    velocity: Vector2f,
+    /// Last cursor position and its estimated speed, for the One Euro cutoff
+    raw: Vector2f,
+    raw_velocity: Vector2f,
+    output: Vector2f,
+}
+
+impl HandSmoother {
+    pub fn new(settings: &SmoothingSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            filtered: None,
+            velocity: Vector2f::default(),
+            raw: Vector2f::default(),
+            raw_velocity: Vector2f::default(),
+            output: Vector2f::default(),
+        }
+    }
+
+    /// Where the hand was put by the last update
+    pub fn position(&self) -> Vector2f {
+        self.output
+    }
+
+    /// Moves the hand towards `target` for a frame drawn at `now`
+    pub fn update(&mut self, target: Vector2f, now: Instant) -> Vector2f {
+        let (position, last) = match self.filtered {
+            Some(filtered) => filtered,
+            None => {
+                self.filtered = Some((target, now));
+                self.raw = target;
+                self.output = target;
+                return target;
+            }
+        };
+        let dt = now.saturating_duration_since(last).as_secs_f32();
+        if dt <= 0.0 {
+            return self.output;
+        }
+
+        let settings = &self.settings;
+        let derivative = low_pass(settings.derivative_cutoff, dt);
+        self.raw_velocity += ((target - self.raw) / dt - self.raw_velocity) * derivative;
+        self.raw = target;
+        let (mut next, mut velocity) = match settings.filter {
+            HandFilter::None => (target, self.raw_velocity),
+            HandFilter::Exponential => {
+                let keep = 0.5f32.powf(dt / settings.half_life.max(1e-3));
+                (target + (position - target) * keep, self.velocity)
+            }
+            HandFilter::Spring => {
+                // Exact step of a critically damped spring, stable for any frame time
+                let omega = settings.frequency.max(1e-3);
+                let offset = position - target;
+                let push = self.velocity + offset * omega;
+                let decay = (-omega * dt).exp();
+                let next = target + (offset + push * dt) * decay;
+                (next, (self.velocity - push * (omega * dt)) * decay)
+            }
+            HandFilter::OneEuro => {
+                let cutoff = settings.min_cutoff + settings.beta * length(self.raw_velocity);
+                (position + (target - position) * low_pass(cutoff, dt), self.velocity)
+            }
+        };
+        if !matches!(settings.filter, HandFilter::None | HandFilter::Spring) {
+            velocity += ((next - position) / dt - velocity) * derivative;
+        }
+        if length(target - next) < SETTLE_DISTANCE && length(velocity) < SETTLE_SPEED {
+            next = target;
+            velocity = Vector2f::default();
+        }
+
+        self.filtered = Some((next, now));
+        self.velocity = velocity;
+        let predicted = next + velocity * settings.prediction.max(0.0);
+        self.output = Vector2f::new(predicted.x.clamp(0.0, 1.0), predicted.y.clamp(0.0, 1.0));
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn settings(filter: HandFilter) -> SmoothingSettings {
+        SmoothingSettings {
+            filter,
+            ..Default::default()
+        }
+    }
+
+    /// Feeds `targets` one per 10ms frame and returns the hand positions
+    fn run(smoother: &mut HandSmoother, targets: &[f32]) -> Vec<f32> {
+        let start = Instant::now();
+        targets
+            .iter()
+            .enumerate()
+            .map(|(frame, x)| {
+                let now = start + Duration::from_millis(10 * frame as u64);
+                smoother.update(Vector2f::new(*x, 0.5), now).x
+            })
+            .collect()
+    }
+
+    fn step(frames: usize) -> Vec<f32> {
+        let mut targets = vec![0.0];
+        targets.resize(frames, 1.0);
+        targets
+    }
+
+    #[test]
+    fn test_no_filter_follows_cursor() {
+        let mut smoother = HandSmoother::new(&settings(HandFilter::None));
+        assert_eq!(vec![0.0, 1.0, 1.0], run(&mut smoother, &step(3)));
+    }
+
+    #[test]
+    fn test_exponential_half_life() {
+        let mut smoother = HandSmoother::new(&SmoothingSettings {
+            half_life: 0.02,
+            ..settings(HandFilter::Exponential)
+        });
+        let hand = run(&mut smoother, &step(4));
+        assert!((hand[1] - 0.293).abs() < 0.01, "{:?}", hand);
+        assert!((hand[2] - 0.5).abs() < 0.01, "{:?}", hand);
+    }
+
+    #[test]
+    fn test_spring_settles_without_overshoot() {
+        let mut smoother = HandSmoother::new(&settings(HandFilter::Spring));
+        let hand = run(&mut smoother, &step(100));
+        assert!(hand.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", hand);
+        assert!(hand.iter().all(|x| *x <= 1.0));
+        assert_eq!(1.0, hand[99]);
+    }
+
+    #[test]
+    fn test_one_euro_smooths_jitter_but_follows_moves() {
+        let mut smoother = HandSmoother::new(&settings(HandFilter::OneEuro));
+        let jitter: Vec<f32> = (0..50).map(|frame| 0.5 + 0.01 * (frame % 2) as f32).collect();
+        let hand = run(&mut smoother, &jitter);
+        let spread = hand[40..].iter().fold(0.0f32, |spread, x| spread.max((x - 0.505).abs()));
+        assert!(spread < 0.002, "{:?}", hand);
+
+        // A fast sweep raises the cutoff, so the hand stays close behind
+        let mut smoother = HandSmoother::new(&settings(HandFilter::OneEuro));
+        let sweep: Vec<f32> = (0..20).map(|frame| frame as f32 * 0.05).collect();
+        let hand = run(&mut smoother, &sweep);
+        assert!(sweep[19] - hand[19] < 0.2, "{:?}", hand);
+    }
+
+    #[test]
+    fn test_prediction_leads_steady_movement() {
+        let sweep: Vec<f32> = (0..100).map(|frame| 0.1 + frame as f32 * 0.004).collect();
+        let mut smoother = HandSmoother::new(&SmoothingSettings {
+            prediction: 0.02,
+            ..settings(HandFilter::None)
+        });
+        let hand = run(&mut smoother, &sweep);
+        // Once the speed estimate caught up, 20ms ahead is two frames ahead
+        assert!((hand[99] - (sweep[99] + 0.008)).abs() < 0.0005, "{:?}", hand);
+        assert!(hand[10] > sweep[10]);
+    }
+}
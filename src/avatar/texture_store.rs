@@ -0,0 +1,319 @@
+use log::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use super::{decode_frames, DecodedFrame, Effects, Margin, SfmlResult, TextureFrames};
+use crate::render::Backend;
+use crate::skin::DefaultSkin;
+
+/// Extensions checked, in order, before falling back to the part's `.png` file
+const ANIMATED_EXTENSIONS: [&str; 1] = ["gif"];
+
+/// Where the pixels of a texture come from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum TextureSource {
+    /// Canonical path of an image in the skin directory
+    File(PathBuf),
+    /// Image from the built-in default skin
+    Embedded(String),
+}
+
+impl TextureSource {
+    /// Finds the file a part is loaded from, preferring animated variants over the plain `.png`
+    pub fn resolve(images_path: &Path, image: &str) -> Self {
+        let stem = Path::new(image)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(image);
+        let candidates = ANIMATED_EXTENSIONS
+            .iter()
+            .map(|extension| format!("{}.{}", stem, extension))
+            .chain(std::iter::once(image.to_string()));
+        for candidate in candidates {
+            let path = images_path.join(&candidate);
+            if path.exists() {
+                return Self::File(fs::canonicalize(&path).unwrap_or(path));
+            }
+        }
+        if DefaultSkin::get(image).is_some() {
+            Self::Embedded(image.to_string())
+        } else {
+            Self::File(images_path.join(image))
+        }
+    }
+
+    /// Warns about a part a skin lacks that comes from the built-in skin instead. A missing
+    /// skin directory just means the built-in skin is used, that isn't warned about.
+    fn warn_fallback(&self, images_path: &Path) {
+        if let Self::Embedded(image) = self {
+            if images_path.is_dir() {
+                let expected = images_path.join(image);
+                warn!("No {}, using the built-in {}", expected.display(), image);
+            }
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        match self {
+            Self::File(path) => fs::metadata(path).and_then(|meta| meta.modified()).ok(),
+            Self::Embedded(_) => None,
+        }
+    }
+
+    pub fn read(&self) -> SfmlResult<Vec<u8>> {
+        match self {
+            Self::File(path) => Ok(fs::read(path)?),
+            Self::Embedded(image) => Ok(DefaultSkin::get(image)
+                .map(|file| file.data.into_owned())
+                .unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Display for TextureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Embedded(image) => write!(f, "<built-in>/{}", image),
+        }
+    }
+}
+
+/// A source file together with the processing applied to it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TextureKey {
+    source: TextureSource,
+    effects: String,
+}
+
+impl TextureKey {
+    fn new(source: TextureSource, effects: &Effects) -> Self {
+        Self {
+            source,
+            effects: effects.fingerprint(),
+        }
+    }
+}
+
+impl fmt::Display for TextureKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.effects.is_empty() {
+            write!(f, "{}", self.source)
+        } else {
+            write!(f, "{} (with effects)", self.source)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StoreEntry {
+    modified: Option<SystemTime>,
+    frames: Rc<TextureFrames>,
+}
+
+/// Textures shared by every part, keyed by source file and reloaded only when the file changes
+#[derive(Debug)]
+pub(crate) struct TextureStore {
+    entries: HashMap<TextureKey, StoreEntry>,
+    smooth: bool,
+    backend: Backend,
+}
+
+impl Default for TextureStore {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+            smooth: true,
+            backend: Default::default(),
+        }
+    }
+}
+
+impl TextureStore {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// A store keeping images in memory for `SoftwareRenderer`, usable without a display
+    pub fn software() -> Self {
+        Self {
+            backend: Backend::Software,
+            ..Default::default()
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Switches texture smoothing, textures loaded with the old setting are loaded again
+    pub fn set_smooth(&mut self, smooth: bool) {
+        if self.smooth != smooth {
+            self.entries.clear();
+            self.smooth = smooth;
+        }
+    }
+
+    fn cached(&self, key: &TextureKey, modified: Option<SystemTime>) -> Option<Rc<TextureFrames>> {
+        self.entries
+            .get(key)
+            .filter(|entry| entry.modified == modified)
+            .map(|entry| entry.frames.clone())
+    }
+
+    fn insert(
+        &mut self,
+        key: TextureKey,
+        modified: Option<SystemTime>,
+        (frames, margin): (Vec<DecodedFrame>, Margin),
+    ) -> SfmlResult<Rc<TextureFrames>> {
+        let frames = Rc::new(TextureFrames::new(frames, margin, self.smooth, self.backend)?);
+        self.entries.insert(
+            key,
+            StoreEntry {
+                modified,
+                frames: frames.clone(),
+            },
+        );
+        Ok(frames)
+    }
+
+    pub fn load(&mut self, images_path: &Path, image: &str, effects: &Effects) -> SfmlResult<Rc<TextureFrames>> {
+        let key = TextureKey::new(TextureSource::resolve(images_path, image), effects);
+        let modified = key.source.modified();
+        if let Some(frames) = self.cached(&key, modified) {
+            return Ok(frames);
+        }
+        debug!("Loading texture {}", key);
+        key.source.warn_fallback(images_path);
+        let frames = effects.apply(decode_frames(&key.source.read()?)?);
+        self.insert(key, modified, frames)
+    }
+
+    /// Decodes and processes every changed image on worker threads, then uploads them here on the
+    /// render thread.
+    ///
+    /// `on_progress` is called with the number of images done and the total after each upload.
+    pub fn preload<F>(
+        &mut self,
+        images_path: &Path,
+        images: &[(String, Effects)],
+        mut on_progress: F,
+    ) -> SfmlResult<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut seen = HashSet::new();
+        let pending: Vec<(TextureKey, Effects, Option<SystemTime>)> = images
+            .iter()
+            .map(|(image, effects)| {
+                let key = TextureKey::new(TextureSource::resolve(images_path, image), effects);
+                (key, effects.clone())
+            })
+            .filter(|(key, _)| seen.insert(key.clone()))
+            .map(|(key, effects)| {
+                let modified = key.source.modified();
+                (key, effects, modified)
+            })
+            .filter(|(key, _, modified)| self.cached(key, *modified).is_none())
+            .collect();
+        let total = pending.len();
+        if total == 0 {
+            return Ok(());
+        }
+        let sources: HashSet<&TextureSource> =
+            pending.iter().map(|(key, _, _)| &key.source).collect();
+        for source in sources {
+            source.warn_fallback(images_path);
+        }
+        let workers = thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1)
+            .min(total);
+        debug!("Decoding {} textures on {} threads", total, workers);
+
+        let queue = Arc::new(Mutex::new(pending));
+        let (sender, receiver) = mpsc::channel();
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let queue = queue.clone();
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let next = match queue.lock() {
+                        Ok(mut queue) => queue.pop(),
+                        Err(_) => None,
+                    };
+                    let (key, effects, modified) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let decoded = key
+                        .source
+                        .read()
+                        .and_then(|bytes| decode_frames(&bytes))
+                        .map(|frames| effects.apply(frames));
+                    if sender.send((key, modified, decoded)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        on_progress(0, total);
+        let mut result = Ok(());
+        for (done, (key, modified, decoded)) in receiver.iter().enumerate() {
+            debug!("Uploading texture {}", key);
+            if let Err(err) = decoded.and_then(|frames| self.insert(key, modified, frames)) {
+                // Keep draining so the workers can finish, but remember the first failure
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+            on_progress(done + 1, total);
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+        result
+    }
+
+    /// Drops textures no part holds anymore
+    pub fn prune(&mut self) {
+        self.entries
+            .retain(|_, entry| Rc::strong_count(&entry.frames) > 1);
+    }
+
+    /// Texture memory in bytes for each loaded source, largest first
+    pub fn memory_report(&self) -> Vec<(String, usize, usize)> {
+        let mut report: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    key.to_string(),
+                    entry.frames.frame_count(),
+                    entry.frames.memory_usage(),
+                )
+            })
+            .collect();
+        report.sort_by(|a, b| b.2.cmp(&a.2));
+        report
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.entries
+            .values()
+            .map(|entry| entry.frames.memory_usage())
+            .sum()
+    }
+}
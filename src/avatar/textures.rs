@@ -1,7 +1,7 @@
-use super::{SfmlError, SfmlResult};
-use sfml::graphics::Texture;
-use sfml::SfBox;
-use std::path::{Path, PathBuf};
+use super::{Effects, Part, SfmlResult, TextureStore};
+use crate::Config;
+use std::collections::HashMap;
+use std::path::Path;
 
 const RIGHT_ARM_IMAGE: &str = "arm.png";
 const LEFT_ARM_LEFT_IMAGE: &str = "left.png";
@@ -14,109 +14,262 @@ const MOUSE_L: &str = "mousel.png";
 const MOUSE_R: &str = "mouser.png";
 const MOUSE_LR: &str = "mouselr.png";
 
+/// Whether the background layer is drawn, transparent windows only use one the skin provides
+fn has_background(images_path: &Path, config: &Config) -> bool {
+    !config.window.transparent || images_path.join(BACKGROUND_IMAGE).exists()
+}
+
+/// Every image the rig draws, used to decode them all up front
+pub fn skin_images(config: &Config) -> Vec<String> {
+    [
+        BACKGROUND_IMAGE,
+        AVATAR_IMAGE,
+        RIGHT_ARM_IMAGE,
+        LEFT_ARM_LEFT_IMAGE,
+        LEFT_ARM_RIGHT_IMAGE,
+        LEFT_ARM_UP_IMAGE,
+        MOUSE,
+        MOUSE_L,
+        MOUSE_R,
+        MOUSE_LR,
+    ]
+    .iter()
+    .filter(|image| **image != BACKGROUND_IMAGE || has_background(&config.images_path, config))
+    .map(|image| image.to_string())
+    .chain(config.layer_images())
+    .chain(config.ik_arm_images())
+    .collect()
+}
+
+/// Every image the rig draws with the effects baked into it
+pub fn skin_textures(config: &Config) -> Vec<(String, Effects)> {
+    skin_images(config)
+        .into_iter()
+        .map(|image| {
+            let effects = Effects::for_part(config, part_name(&image));
+            (image, effects)
+        })
+        .collect()
+}
+
+/// Name used for a part in the config, the image file name without its extension
+pub fn part_name(image: &str) -> &str {
+    Path::new(image)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(image)
+}
+
 pub trait TextureContainer {
-    fn reload_textures(&mut self, images_path: &Path) -> SfmlResult<()>;
-    fn load_texture_from_file(images_path: &Path, image: &str) -> SfmlResult<SfBox<Texture>> {
-        let mut image_path = PathBuf::from(images_path);
-        image_path.push(image);
-        let texture = Texture::from_file(image_path.to_str().ok_or(SfmlError::PathConversion)?)?;
-        Ok(texture)
+    fn reload_textures(
+        &mut self,
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<()>;
+    /// Every part in the container, to follow their animations
+    fn parts(&self) -> Vec<&Part>;
+    fn load_texture_from_file(
+        store: &mut TextureStore,
+        images_path: &Path,
+        image: &str,
+        config: &Config,
+    ) -> SfmlResult<Part> {
+        let name = part_name(image);
+        let settings = config.part_settings(name);
+        let frames = store.load(images_path, image, &Effects::for_part(config, name))?;
+        Ok(Part::new(frames, &settings))
     }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct AvatarTextures {
-    pub background: SfBox<Texture>,
-    pub avatar: SfBox<Texture>,
+    pub background: Option<Part>,
+    pub avatar: Part,
+    /// Images of the config's image layers, by file name
+    pub overlays: HashMap<String, Part>,
 }
 
 impl AvatarTextures {
-    pub fn new(images_path: &Path) -> SfmlResult<Self> {
-        let background = Self::load_texture_from_file(images_path, BACKGROUND_IMAGE)?;
-        let avatar = Self::load_texture_from_file(images_path, AVATAR_IMAGE)?;
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let background = Self::load_background(store, images_path, config)?;
+        let avatar = Self::load_texture_from_file(store, images_path, AVATAR_IMAGE, config)?;
+        let overlays = Self::load_overlays(store, images_path, config)?;
 
-        Ok(Self { background, avatar })
+        Ok(Self {
+            background,
+            avatar,
+            overlays,
+        })
+    }
+
+    fn load_overlays(
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<HashMap<String, Part>> {
+        config
+            .layer_images()
+            .into_iter()
+            .map(|image| {
+                let part = Self::load_texture_from_file(store, images_path, &image, config)?;
+                Ok((image, part))
+            })
+            .collect()
+    }
+
+    fn load_background(
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<Option<Part>> {
+        if !has_background(images_path, config) {
+            return Ok(None);
+        }
+        let background = Self::load_texture_from_file(store, images_path, BACKGROUND_IMAGE, config)?;
+        Ok(Some(background))
     }
 }
 
 impl TextureContainer for AvatarTextures {
-    fn reload_textures(&mut self, images_path: &Path) -> SfmlResult<()> {
-        let background = Self::load_texture_from_file(images_path, BACKGROUND_IMAGE)?;
-        let avatar = Self::load_texture_from_file(images_path, AVATAR_IMAGE)?;
+    fn reload_textures(
+        &mut self,
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<()> {
+        let background = Self::load_background(store, images_path, config)?;
+        let avatar = Self::load_texture_from_file(store, images_path, AVATAR_IMAGE, config)?;
+        let overlays = Self::load_overlays(store, images_path, config)?;
         self.background = background;
         self.avatar = avatar;
+        self.overlays = overlays;
         Ok(())
     }
+
+    fn parts(&self) -> Vec<&Part> {
+        let mut parts: Vec<&Part> = self.background.iter().collect();
+        parts.push(&self.avatar);
+        parts.extend(self.overlays.values());
+        parts
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct ArmTextures {
     pub left: LeftArmTextures,
-    pub right: SfBox<Texture>,
+    pub right: Part,
+    /// Upper arm and forearm, while the two-bone arm is enabled
+    pub segments: Option<(Part, Part)>,
 }
 
 impl ArmTextures {
-    pub fn new(images_path: &Path) -> SfmlResult<Self> {
-        let right = Self::load_texture_from_file(images_path, RIGHT_ARM_IMAGE)?;
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let right = Self::load_texture_from_file(store, images_path, RIGHT_ARM_IMAGE, config)?;
+        let segments = Self::load_segments(store, images_path, config)?;
+
+        let left = LeftArmTextures::new(store, images_path, config)?;
+        Ok(Self {
+            right,
+            left,
+            segments,
+        })
+    }
 
-        let left = LeftArmTextures::new(images_path)?;
-        Ok(Self { right, left })
+    fn load_segments(
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<Option<(Part, Part)>> {
+        if !config.ik_arm.enabled {
+            return Ok(None);
+        }
+        let arm = &config.ik_arm;
+        let upper = Self::load_texture_from_file(store, images_path, &arm.upper_arm, config)?;
+        let forearm = Self::load_texture_from_file(store, images_path, &arm.forearm, config)?;
+        Ok(Some((upper, forearm)))
     }
 }
 
 impl TextureContainer for ArmTextures {
-    fn reload_textures(&mut self, images_path: &Path) -> SfmlResult<()> {
-        let right = Self::load_texture_from_file(images_path, RIGHT_ARM_IMAGE)?;
+    fn reload_textures(
+        &mut self,
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<()> {
+        let right = Self::load_texture_from_file(store, images_path, RIGHT_ARM_IMAGE, config)?;
+        let segments = Self::load_segments(store, images_path, config)?;
         self.right = right;
-        self.left.reload_textures(images_path)?;
+        self.segments = segments;
+        self.left.reload_textures(store, images_path, config)?;
         Ok(())
     }
+
+    fn parts(&self) -> Vec<&Part> {
+        let mut parts = self.left.parts();
+        parts.push(&self.right);
+        if let Some((upper, forearm)) = &self.segments {
+            parts.push(upper);
+            parts.push(forearm);
+        }
+        parts
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct LeftArmTextures {
-    pub left: SfBox<Texture>,
-    pub right: SfBox<Texture>,
-    pub up: SfBox<Texture>,
+    pub left: Part,
+    pub right: Part,
+    pub up: Part,
 }
 
 impl LeftArmTextures {
-    pub fn new(images_path: &Path) -> SfmlResult<Self> {
-        let left = Self::load_texture_from_file(images_path, LEFT_ARM_LEFT_IMAGE)?;
-        let right = Self::load_texture_from_file(images_path, LEFT_ARM_RIGHT_IMAGE)?;
-        let up = Self::load_texture_from_file(images_path, LEFT_ARM_UP_IMAGE)?;
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let left = Self::load_texture_from_file(store, images_path, LEFT_ARM_LEFT_IMAGE, config)?;
+        let right = Self::load_texture_from_file(store, images_path, LEFT_ARM_RIGHT_IMAGE, config)?;
+        let up = Self::load_texture_from_file(store, images_path, LEFT_ARM_UP_IMAGE, config)?;
 
         Ok(Self { left, right, up })
     }
 }
 
 impl TextureContainer for LeftArmTextures {
-    fn reload_textures(&mut self, images_path: &Path) -> SfmlResult<()> {
-        let left = Self::load_texture_from_file(images_path, LEFT_ARM_LEFT_IMAGE)?;
-        let right = Self::load_texture_from_file(images_path, LEFT_ARM_RIGHT_IMAGE)?;
-        let up = Self::load_texture_from_file(images_path, LEFT_ARM_UP_IMAGE)?;
+    fn reload_textures(
+        &mut self,
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<()> {
+        let left = Self::load_texture_from_file(store, images_path, LEFT_ARM_LEFT_IMAGE, config)?;
+        let right = Self::load_texture_from_file(store, images_path, LEFT_ARM_RIGHT_IMAGE, config)?;
+        let up = Self::load_texture_from_file(store, images_path, LEFT_ARM_UP_IMAGE, config)?;
 
         self.left = left;
         self.right = right;
         self.up = up;
         Ok(())
     }
+
+    fn parts(&self) -> Vec<&Part> {
+        vec![&self.left, &self.right, &self.up]
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct MouseTextures {
-    pub mouse: SfBox<Texture>,
-    pub mouse_l: SfBox<Texture>,
-    pub mouse_r: SfBox<Texture>,
-    pub mouse_lr: SfBox<Texture>,
+    pub mouse: Part,
+    pub mouse_l: Part,
+    pub mouse_r: Part,
+    pub mouse_lr: Part,
 }
 
 impl MouseTextures {
-    pub fn new(images_path: &Path) -> SfmlResult<Self> {
-        let mouse = Self::load_texture_from_file(images_path, MOUSE)?;
-        let mouse_l = Self::load_texture_from_file(images_path, MOUSE_L)?;
-        let mouse_r = Self::load_texture_from_file(images_path, MOUSE_R)?;
-        let mouse_lr = Self::load_texture_from_file(images_path, MOUSE_LR)?;
+    pub fn new(store: &mut TextureStore, images_path: &Path, config: &Config) -> SfmlResult<Self> {
+        let mouse = Self::load_texture_from_file(store, images_path, MOUSE, config)?;
+        let mouse_l = Self::load_texture_from_file(store, images_path, MOUSE_L, config)?;
+        let mouse_r = Self::load_texture_from_file(store, images_path, MOUSE_R, config)?;
+        let mouse_lr = Self::load_texture_from_file(store, images_path, MOUSE_LR, config)?;
 
         Ok(Self {
             mouse,
@@ -128,11 +281,16 @@ impl MouseTextures {
 }
 
 impl TextureContainer for MouseTextures {
-    fn reload_textures(&mut self, images_path: &Path) -> SfmlResult<()> {
-        let mouse = Self::load_texture_from_file(images_path, MOUSE)?;
-        let mouse_l = Self::load_texture_from_file(images_path, MOUSE_L)?;
-        let mouse_r = Self::load_texture_from_file(images_path, MOUSE_R)?;
-        let mouse_lr = Self::load_texture_from_file(images_path, MOUSE_LR)?;
+    fn reload_textures(
+        &mut self,
+        store: &mut TextureStore,
+        images_path: &Path,
+        config: &Config,
+    ) -> SfmlResult<()> {
+        let mouse = Self::load_texture_from_file(store, images_path, MOUSE, config)?;
+        let mouse_l = Self::load_texture_from_file(store, images_path, MOUSE_L, config)?;
+        let mouse_r = Self::load_texture_from_file(store, images_path, MOUSE_R, config)?;
+        let mouse_lr = Self::load_texture_from_file(store, images_path, MOUSE_LR, config)?;
 
         self.mouse = mouse;
         self.mouse_l = mouse_l;
@@ -141,4 +299,8 @@ impl TextureContainer for MouseTextures {
 
         Ok(())
     }
+
+    fn parts(&self) -> Vec<&Part> {
+        vec![&self.mouse, &self.mouse_l, &self.mouse_r, &self.mouse_lr]
+    }
 }
@@ -0,0 +1,307 @@
+use image::RgbaImage;
+use log::{info, warn};
+use sfml::graphics::{Color, FloatRect, RenderTarget, RenderTexture};
+use sfml::system::{Vector2f, Vector2u};
+use std::fmt;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{offscreen_avatar, scene_size, Avatar, ClipAction, SfmlError};
+use crate::errors::Result;
+use crate::config::ShaderPass;
+use crate::render::{
+    texture_pixels, Backend, RenderError, Renderer, SfmlRenderer, ShaderChain, ShaderInputs,
+    SoftwareRenderer, VideoWriter,
+};
+use crate::user_input::UserInputMonitor;
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::config::StreamSettings;
+use crate::stream::StreamServer;
+use crate::{Config, PTuberError};
+
+/// Somewhere frames go besides the window, each at its own framerate
+pub trait SceneOutput: fmt::Debug {
+    /// Draws and sends a frame if one is due
+    fn capture(
+        &mut self,
+        avatar: &mut Avatar,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+        now: Instant,
+    ) -> Result<()>;
+
+    /// How long until the next frame is due
+    fn wait(&self, now: Instant) -> Duration;
+}
+
+/// Draws the scene at its own size, apart from the window and its letterboxing.
+///
+/// Skins loaded as textures also get the config's shaders, the software renderer can't run
+/// them. `offscreen_avatar` loads skins as textures whenever there are shaders.
+#[derive(Default)]
+pub struct SceneCapture {
+    /// Offscreen target for skins loaded as textures
+    target: Option<RenderTexture>,
+    shaders: ShaderChain,
+    /// The passes `shaders` was loaded from, to notice config changes
+    shader_passes: Vec<ShaderPass>,
+}
+
+impl SceneCapture {
+    pub fn draw(
+        &mut self,
+        avatar: &mut Avatar,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+    ) -> Result<RgbaImage> {
+        let scene = scene_size(avatar.config());
+        let size = Vector2u::new(scene.x as u32, scene.y as u32);
+        let full = FloatRect::new(0.0, 0.0, 1.0, 1.0);
+        if avatar.texture_store().backend() == Backend::Software {
+            let mut renderer = SoftwareRenderer::new(size.x, size.y);
+            renderer.set_view(scene, full);
+            renderer.clear(background);
+            avatar.draw(&mut renderer, keyboard, mouse)?;
+            renderer.present();
+            return Ok(renderer.into_image());
+        }
+
+        if avatar.config().shaders != self.shader_passes {
+            self.shaders = ShaderChain::load(avatar.config());
+            self.shader_passes = avatar.config().shaders.clone();
+        }
+        let target = match self.target.take() {
+            Some(target) if target.size() == size => target,
+            _ => RenderTexture::new(size.x, size.y).ok_or(SfmlError::RenderTexture)?,
+        };
+        let target = self.target.insert(target);
+        let scene_target = if self.shaders.is_empty() {
+            None
+        } else {
+            Some(self.shaders.scene_target(size).ok_or(SfmlError::RenderTexture)?)
+        };
+        match scene_target {
+            Some(scene_target) => {
+                let mut renderer = SfmlRenderer::new(scene_target);
+                renderer.set_view(scene, full);
+                renderer.clear(background);
+                avatar.draw(&mut renderer, keyboard, mouse)?;
+                renderer.present();
+                let inputs = ShaderInputs::capture(keyboard, mouse);
+                self.shaders.apply(target, Vector2f::default(), inputs);
+                target.display();
+            }
+            None => {
+                let mut renderer = SfmlRenderer::new(target);
+                renderer.set_view(scene, full);
+                renderer.clear(background);
+                avatar.draw(&mut renderer, keyboard, mouse)?;
+                renderer.present();
+            }
+        }
+        let image = texture_pixels(target.texture()).ok_or(SfmlError::ImageCreate)?;
+        Ok(image)
+    }
+}
+
+/// Writes the scene as a video stream at a fixed framerate
+pub struct VideoOutput {
+    writer: VideoWriter,
+    interval: Duration,
+    /// When the next frame is due, from the first capture on
+    next: Option<Instant>,
+    scene: SceneCapture,
+}
+
+impl fmt::Debug for VideoOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoOutput")
+            .field("interval", &self.interval)
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VideoOutput {
+    pub fn new(writer: VideoWriter) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / writer.fps(),
+            writer,
+            next: None,
+            scene: Default::default(),
+        }
+    }
+
+    /// Frames the stream is owed at `now`, more than one when drawing fell behind
+    fn frames_due(&mut self, now: Instant) -> u32 {
+        let next = self.next.get_or_insert(now);
+        if now < *next {
+            return 0;
+        }
+        let behind = (now - *next).as_nanos() / self.interval.as_nanos();
+        let frames = behind as u32 + 1;
+        *next += self.interval * frames;
+        frames
+    }
+}
+
+impl SceneOutput for VideoOutput {
+    /// Repeats the frame for frames that were missed, to keep the stream's timing
+    fn capture(
+        &mut self,
+        avatar: &mut Avatar,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+        now: Instant,
+    ) -> Result<()> {
+        let frames = self.frames_due(now);
+        if frames == 0 {
+            return Ok(());
+        }
+        let image = self.scene.draw(avatar, keyboard, mouse, background)?;
+        self.writer.write_frame(&image, frames)?;
+        Ok(())
+    }
+
+    fn wait(&self, now: Instant) -> Duration {
+        self.next
+            .map(|next| next.saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+}
+
+/// Hands frames to the HTTP stream, only while someone is watching
+pub struct StreamOutput {
+    server: StreamServer,
+    interval: Duration,
+    next: Instant,
+    scene: SceneCapture,
+}
+
+impl fmt::Debug for StreamOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamOutput")
+            .field("interval", &self.interval)
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamOutput {
+    /// Starts the server, its control endpoints send to `control`
+    pub fn start(settings: &StreamSettings, control: Sender<ClipAction>) -> Result<Self> {
+        Ok(Self {
+            server: StreamServer::start(settings, Some(control))?,
+            interval: settings.frame_interval(),
+            next: Instant::now(),
+            scene: Default::default(),
+        })
+    }
+}
+
+impl SceneOutput for StreamOutput {
+    fn capture(
+        &mut self,
+        avatar: &mut Avatar,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+        now: Instant,
+    ) -> Result<()> {
+        if now < self.next || !self.server.wanted() {
+            return Ok(());
+        }
+        self.next = now + self.interval;
+        let image = self.scene.draw(avatar, keyboard, mouse, background)?;
+        self.server.publish(image);
+        Ok(())
+    }
+
+    fn wait(&self, now: Instant) -> Duration {
+        self.next.saturating_duration_since(now)
+    }
+}
+
+/// Captures a frame for each output that is due, dropping outputs that can't take any more
+pub fn capture_outputs(
+    outputs: &mut Vec<Box<dyn SceneOutput>>,
+    avatar: &mut Avatar,
+    keyboard: &KeyboardViewModelImpl,
+    mouse: &DeviceViewModelImpl,
+    background: Color,
+    now: Instant,
+) {
+    outputs.retain_mut(
+        |output| match output.capture(avatar, keyboard, mouse, background, now) {
+            Ok(()) => true,
+            Err(PTuberError::Render(RenderError::Closed)) => {
+                info!("Video reader closed the stream");
+                false
+            }
+            Err(err) => {
+                warn!("Stopping {:?}: {:?}", output, err);
+                false
+            }
+        },
+    );
+}
+
+/// How long until any of `outputs` is due, `limit` at most
+pub fn outputs_wait(outputs: &[Box<dyn SceneOutput>], now: Instant, limit: Duration) -> Duration {
+    outputs
+        .iter()
+        .map(|output| output.wait(now))
+        .fold(limit, Duration::min)
+}
+
+/// Runs without a window, reading input and feeding `outputs` until none is left
+pub fn run_headless(
+    config: Config,
+    mut outputs: Vec<Box<dyn SceneOutput>>,
+    keyboard: &KeyboardViewModelImpl,
+    mouse: &DeviceViewModelImpl,
+    events: &mut UserInputMonitor,
+) -> Result<()> {
+    let background = config.background.clone().into();
+    let poll_interval = config.render.poll_interval();
+    let mut avatar = offscreen_avatar(config)?;
+    let mut next_poll = Instant::now();
+    while !outputs.is_empty() {
+        let now = Instant::now();
+        if now >= next_poll {
+            events.get_events();
+            next_poll = now + poll_interval;
+        }
+        capture_outputs(&mut outputs, &mut avatar, keyboard, mouse, background, now);
+        let now = Instant::now();
+        let wait = outputs_wait(&outputs, now, next_poll.saturating_duration_since(now));
+        thread::sleep(wait);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::VideoFormat;
+
+    #[test]
+    fn test_frames_due_catch_up() {
+        // The process id keeps test runs going at the same time apart
+        let name = format!("ptuber_video_test_{}.y4m", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let writer = VideoWriter::open(path.to_str().unwrap(), VideoFormat::Y4m, 10).unwrap();
+        let mut video = VideoOutput::new(writer);
+        let start = Instant::now();
+        assert_eq!(1, video.frames_due(start));
+        assert_eq!(0, video.frames_due(start + Duration::from_millis(50)));
+        assert_eq!(1, video.frames_due(start + Duration::from_millis(100)));
+        assert_eq!(3, video.frames_due(start + Duration::from_millis(420)));
+        assert_eq!(Duration::from_millis(80), video.wait(start + Duration::from_millis(420)));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,63 @@
+use sfml::graphics::{FloatRect, RenderTarget, RenderWindow, View};
+use sfml::system::{Vector2f, Vector2u};
+
+use crate::Config;
+
+/// Size of the scene the skin is drawn in, independent of the window's size
+pub fn scene_size(config: &Config) -> Vector2f {
+    Vector2f::new(config.window.width as f32, config.window.height as f32)
+}
+
+/// Part of the window, as fractions of its size, that shows the whole scene at its aspect ratio.
+///
+/// With `integer_scale` the scene is only enlarged by whole numbers so pixel art stays crisp.
+pub fn letterbox(window: Vector2u, scene: Vector2f, integer_scale: bool) -> FloatRect {
+    let (width, height) = (window.x.max(1) as f32, window.y.max(1) as f32);
+    let mut scale = (width / scene.x).min(height / scene.y);
+    if integer_scale && scale >= 1.0 {
+        scale = scale.floor();
+    }
+    let fraction = Vector2f::new(scene.x * scale / width, scene.y * scale / height);
+    FloatRect::new(
+        (1.0 - fraction.x) / 2.0,
+        (1.0 - fraction.y) / 2.0,
+        fraction.x,
+        fraction.y,
+    )
+}
+
+/// Shows the `scene` sized area at the origin in the middle of the window, bars are left uncovered
+pub fn apply_letterbox(window: &mut RenderWindow, scene: Vector2f, integer_scale: bool) {
+    let mut view = View::new(Vector2f::new(scene.x / 2.0, scene.y / 2.0), scene);
+    view.set_viewport(letterbox(window.size(), scene, integer_scale));
+    window.set_view(&view);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letterbox_same_aspect_fills_window() {
+        let viewport = letterbox(Vector2u::new(1224, 934), Vector2f::new(612.0, 467.0), false);
+        assert_eq!(FloatRect::new(0.0, 0.0, 1.0, 1.0), viewport);
+    }
+
+    #[test]
+    fn test_letterbox_wide_window_gets_side_bars() {
+        let viewport = letterbox(Vector2u::new(400, 100), Vector2f::new(100.0, 100.0), false);
+        assert_eq!(FloatRect::new(0.375, 0.0, 0.25, 1.0), viewport);
+    }
+
+    #[test]
+    fn test_letterbox_integer_scale() {
+        let viewport = letterbox(Vector2u::new(256, 256), Vector2f::new(96.0, 96.0), true);
+        assert_eq!(FloatRect::new(0.125, 0.125, 0.75, 0.75), viewport);
+    }
+
+    #[test]
+    fn test_letterbox_integer_scale_shrinks_when_too_small() {
+        let viewport = letterbox(Vector2u::new(50, 50), Vector2f::new(100.0, 100.0), true);
+        assert_eq!(FloatRect::new(0.0, 0.0, 1.0, 1.0), viewport);
+    }
+}
@@ -1,25 +1,52 @@
-use super::Avatar;
+use super::{
+    apply_letterbox, capture_outputs, draw_loading_screen, letterbox, outputs_wait, scene_size,
+    skin_textures, Avatar, CalibrationEditor, ClipAction, FrameState, Hud, Pacer, Redraw,
+    SceneOutput, TextureStore, WindowState,
+};
+use crate::render::{Renderer, SfmlRenderer, ShaderChain, ShaderInputs};
 use crate::user_input::UserInputMonitor;
-use crate::{Config, DEFAULT_CONFIG_NAME, DEFAULT_SKIN_DIR_NAME, MAX_FRAMERATE};
+use crate::{get_window_finder, WindowFinder, WindowFinderImpl};
+use crate::config::OverlaySettings;
+use crate::{Config, DEFAULT_CONFIG_NAME, DEFAULT_SKIN_DIR_NAME};
 use crate::{DeviceViewModelImpl, KeyboardViewModelImpl, PTuberError, PtuberResult};
-use log::debug;
+use log::{debug, info, warn};
 use rust_embed::RustEmbed;
-use sfml::graphics::{Image, RenderTarget, RenderWindow};
-use sfml::window::{Event, Key, Style};
+use sfml::graphics::{Color, FloatRect, Image, RenderTarget, RenderWindow};
+use sfml::system::{Vector2f, Vector2i, Vector2u};
+use sfml::window::{mouse, Event, Key, Style, VideoMode};
 use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const EMBEDDED_ICON_PATH: &str = "icon.png";
+const WINDOW_TITLE: &str = "Ptuber Rigger!";
+/// How long the window manager's answer on whether the window is shown is trusted
+const VISIBILITY_CHECK_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
-#[include = "*.png"]
+#[include = "icon.png"]
 struct Assets;
 
 #[derive(Debug)]
-pub struct PtuberWindow<'a> {
+pub struct PtuberWindow {
     window: RenderWindow,
-    avatar: Avatar<'a>,
+    avatar: Avatar,
     icon: Image,
+    finder: WindowFinderImpl,
+    hud: Hud,
+    editor: CalibrationEditor,
+    /// Video and stream outputs drawn besides the window
+    outputs: Vec<Box<dyn SceneOutput>>,
+    /// Where the screenshot hotkeys send their actions
+    clip_control: Option<Sender<ClipAction>>,
+    /// Post-processing passes for what the window shows, outputs load their own
+    shaders: ShaderChain,
+    /// Events that came in while a loading screen was up, handled before polling new ones
+    queued_events: Vec<Event>,
+    /// Last answer to whether the window is shown, and when it was asked
+    visibility: Option<(bool, Instant)>,
 }
 
 // fn is_left_key(key: Key) -> bool {
@@ -40,24 +67,257 @@ pub struct PtuberWindow<'a> {
 //     }
 // }
 
-impl<'a> PtuberWindow<'a> {
+/// Where `viewport` lands in a window of `size`, in whole pixels
+fn viewport_pixels(size: Vector2u, viewport: FloatRect) -> (Vector2f, Vector2u) {
+    let (width, height) = (size.x as f32, size.y as f32);
+    let position = Vector2f::new((viewport.left * width).round(), (viewport.top * height).round());
+    let pixels = Vector2u::new(
+        (viewport.width * width).round() as u32,
+        (viewport.height * height).round() as u32,
+    );
+    (position, pixels)
+}
+
+/// Opens the window, with an alpha channel when the config asks for transparency and the
+/// platform can provide one
+fn create_window(config: &Config, finder: &WindowFinderImpl) -> RenderWindow {
+    if config.window.transparent {
+        let mode: VideoMode = config.window.clone().into();
+        let borderless = config.overlay.borderless;
+        match finder.create_transparent_window(mode.width, mode.height, WINDOW_TITLE, borderless) {
+            // SAFETY: the handle is a freshly created, open window that nothing else renders to
+            Ok(Some(handle)) => {
+                return unsafe { RenderWindow::from_handle(handle, &Default::default()) }
+            }
+            Ok(None) => warn!("No transparent window support, using a normal window"),
+            Err(err) => warn!("Could not create a transparent window: {:?}", err),
+        }
+    }
+    let mut style = if config.overlay.borderless {
+        Style::NONE
+    } else {
+        Style::TITLEBAR | Style::CLOSE
+    };
+    if config.window.resizable && !config.overlay.borderless {
+        style |= Style::RESIZE;
+    }
+    RenderWindow::new(
+        config.window.clone(),
+        WINDOW_TITLE,
+        style,
+        &Default::default(),
+    )
+}
+
+impl PtuberWindow {
     pub fn new(skin_path: &Path, config: Config) -> PtuberResult<Self> {
-        let mut window = RenderWindow::new(
-            config.window.clone(),
-            "Ptuber Rigger!",
-            Style::TITLEBAR | Style::CLOSE,
-            &Default::default(),
-        );
+        let finder = get_window_finder()?;
+        let mut window = create_window(&config, &finder);
         let icon_bytes = Assets::get(EMBEDDED_ICON_PATH).ok_or(PTuberError::AssetGet)?;
         debug!("Icon Bytes: {}", icon_bytes.data.len());
         let icon = Image::from_memory(&icon_bytes.data).ok_or(PTuberError::AssetLoad)?;
-        window.set_framerate_limit(MAX_FRAMERATE);
-        let avatar = Avatar::new(skin_path, config)?;
-        Ok(Self {
+
+        if config.overlay.remember_position {
+            let state = WindowState::load(&WindowState::path(&config.config_path));
+            let monitors = finder.monitors().unwrap_or_default();
+            if let Some(position) = state.and_then(|state| state.restore(&monitors)) {
+                window.set_position(position);
+            }
+        }
+
+        let background: Color = config.background.clone().into();
+        apply_letterbox(&mut window, scene_size(&config), config.window.pixel_art);
+        let mut store = TextureStore::new();
+        store.set_smooth(!config.window.pixel_art);
+        let mut queued_events = Vec::new();
+        store.preload(skin_path, &skin_textures(&config), |done, total| {
+            draw_loading_screen(&mut window, &mut queued_events, background, done, total)
+        })?;
+        let editor = CalibrationEditor::new(&config);
+        let shaders = ShaderChain::load(&config);
+        let avatar = Avatar::new(store, skin_path, config)?;
+        let ptuber_window = Self {
             window,
             avatar,
             icon,
-        })
+            finder,
+            hud: Hud::new()?,
+            editor,
+            outputs: Vec::new(),
+            clip_control: None,
+            shaders,
+            queued_events,
+            visibility: None,
+        };
+        ptuber_window.apply_overlay(&Default::default());
+        Ok(ptuber_window)
+    }
+
+    /// Also draws the scene into `output`, at the output's own framerate
+    pub fn add_output(&mut self, output: Box<dyn SceneOutput>) {
+        self.outputs.push(output);
+    }
+
+    /// Lets F12 and Shift+F12 take screenshots and save replays
+    pub fn set_clip_control(&mut self, control: Sender<ClipAction>) {
+        self.clip_control = Some(control);
+    }
+
+    fn request_clip(&self, action: ClipAction) {
+        match &self.clip_control {
+            Some(control) => {
+                if control.send(action).is_err() {
+                    warn!("Screenshots are no longer saved");
+                }
+            }
+            None => warn!("Screenshots are not set up"),
+        }
+    }
+
+    /// Applies the overlay settings that changed since `previous` through the window manager,
+    /// which not every desktop supports
+    fn apply_overlay(&self, previous: &OverlaySettings) {
+        let overlay = &self.avatar.config().overlay;
+        let handle = self.window.system_handle();
+        if overlay.always_on_top != previous.always_on_top {
+            if let Err(err) = self.finder.set_always_on_top(handle, overlay.always_on_top) {
+                warn!("Could not change always on top: {:?}", err);
+            }
+        }
+        if overlay.click_through != previous.click_through {
+            if let Err(err) = self.finder.set_click_through(handle, overlay.click_through) {
+                warn!("Could not change click through: {:?}", err);
+            }
+        }
+    }
+
+    fn save_window_state(&self) {
+        let config = self.avatar.config();
+        if !config.overlay.remember_position {
+            return;
+        }
+        let monitors = self.finder.monitors().unwrap_or_default();
+        let state = WindowState::capture(self.window.position(), &monitors);
+        if let Err(err) = state.save(&WindowState::path(&config.config_path)) {
+            warn!("Could not save window position: {:?}", err);
+        }
+    }
+
+    /// The debug marks can be dragged only in debug mode, the HUD just shows them
+    fn is_editing(&self) -> bool {
+        self.avatar.config().debug
+    }
+
+    /// Point on the skin canvas under the window pixel at `x`, `y`
+    fn canvas_point(&self, x: i32, y: i32) -> (f32, f32) {
+        let point = self
+            .window
+            .map_pixel_to_coords_current_view(Vector2i::new(x, y));
+        (point.x, point.y)
+    }
+
+    fn apply_calibration(&mut self) {
+        let mut config = self.avatar.config().clone();
+        self.editor.current().apply_to(&mut config);
+        self.avatar.update_calibration(config);
+    }
+
+    fn save_calibration(&self) {
+        let config = self.avatar.config();
+        match config.save_calibration(&config.config_path) {
+            Ok(()) => info!("Saved calibration to {:?}", config.config_path),
+            Err(err) => warn!("Could not save calibration: {:?}", err),
+        }
+    }
+
+    /// Asks the window manager at most once per `VISIBILITY_CHECK_INTERVAL`
+    fn is_visible(&mut self, now: Instant) -> bool {
+        if let Some((visible, checked)) = self.visibility {
+            if now.saturating_duration_since(checked) < VISIBILITY_CHECK_INTERVAL {
+                return visible;
+            }
+        }
+        let visible = match self.finder.is_window_visible(self.window.system_handle()) {
+            Ok(visible) => visible,
+            Err(err) => {
+                debug!("Could not tell if the window is visible: {:?}", err);
+                true
+            }
+        };
+        self.visibility = Some((visible, now));
+        visible
+    }
+
+    /// Draws the scene unless the window is hidden or, with `on_change`, nothing moved
+    fn draw_frame(
+        &mut self,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+        background: Color,
+        redraw: &mut Redraw<FrameState>,
+        now: Instant,
+    ) -> PtuberResult<()> {
+        if self.avatar.config().render.pause_hidden && !self.is_visible(now) {
+            redraw.invalidate();
+            return Ok(());
+        }
+        let render = &self.avatar.config().render;
+        // The HUD's metrics change every frame
+        if render.on_change && !self.hud.is_visible() {
+            let state = self.avatar.frame_state(keyboard, mouse);
+            if !redraw.update(state, now) {
+                return Ok(());
+            }
+        }
+
+        // Only the drawing itself is timed, not the input polling and events before it
+        let started = Instant::now();
+        let config = self.avatar.config();
+        let scene = scene_size(config);
+        let viewport = letterbox(self.window.size(), scene, config.window.pixel_art);
+        let editing = self.is_editing();
+        let pixels = viewport_pixels(self.window.size(), viewport);
+        let target = if self.shaders.is_empty() {
+            None
+        } else {
+            self.shaders.scene_target(pixels.1)
+        };
+        let shaded = match target {
+            Some(target) => {
+                let mut renderer = SfmlRenderer::new(target);
+                renderer.set_view(scene, FloatRect::new(0.0, 0.0, 1.0, 1.0));
+                renderer.clear(background);
+                self.avatar.draw(&mut renderer, keyboard, mouse)?;
+                renderer.present();
+                true
+            }
+            None => false,
+        };
+        if shaded {
+            self.window.clear(background);
+            let inputs = ShaderInputs::capture(keyboard, mouse);
+            self.shaders.apply(&mut self.window, pixels.0, inputs);
+        }
+        let mut renderer = SfmlRenderer::new(&mut self.window);
+        renderer.set_view(scene, viewport);
+        if !shaded {
+            // Clearing ignores the view, so the letterbox bars get the background color too
+            renderer.clear(background);
+            self.avatar.draw(&mut renderer, keyboard, mouse)?;
+        }
+        if editing || self.hud.is_visible() {
+            self.avatar.draw_debug(&mut renderer);
+        }
+        if editing {
+            self.editor.draw(&mut renderer);
+        }
+        if self.hud.is_visible() {
+            let hand = self.avatar.hand_position();
+            self.hud.draw(renderer.target(), keyboard, mouse, hand);
+        }
+        renderer.present();
+        self.hud.record_frame(now, started.elapsed());
+        Ok(())
     }
 
     pub fn display(
@@ -67,17 +327,26 @@ impl<'a> PtuberWindow<'a> {
         events: &mut UserInputMonitor
     ) -> PtuberResult<()> {
         let mut reload_config = false;
+        let mut cycle_variant = false;
         let mut background_color = self.avatar.config().background.clone();
         let icon_size = self.icon.size();
         unsafe {
             self.window
                 .set_icon(icon_size.x, icon_size.y, self.icon.pixel_data());
         }
+        let mut pacer = Pacer::new(&self.avatar.config().render, Instant::now());
+        let mut redraw = Redraw::default();
         while self.window.is_open() {
-            events.get_events();
-            while let Some(event) = self.window.poll_event() {
+            let now = Instant::now();
+            if pacer.poll_due(now) {
+                events.get_events();
+                self.hud.record_poll(now, now.elapsed());
+            }
+            let mut queued = std::mem::take(&mut self.queued_events).into_iter();
+            while let Some(event) = queued.next().or_else(|| self.window.poll_event()) {
                 match event {
                     Event::Closed => {
+                        self.save_window_state();
                         self.window.close();
                         return Ok(());
                     }
@@ -85,35 +354,127 @@ impl<'a> PtuberWindow<'a> {
                         code,
                         alt: _alt,
                         ctrl,
-                        shift: _shift,
+                        shift,
                         system: _system,
                     } => {
                         if code == Key::R && ctrl {
                             reload_config = true;
                         }
+                        if ctrl && self.is_editing() {
+                            let changed = match code {
+                                Key::Z if shift => self.editor.redo(),
+                                Key::Z => self.editor.undo(),
+                                Key::Y => self.editor.redo(),
+                                Key::S => {
+                                    self.save_calibration();
+                                    false
+                                }
+                                _ => false,
+                            };
+                            if changed {
+                                self.apply_calibration();
+                                redraw.invalidate();
+                            }
+                        }
+                        if code == Key::V && ctrl {
+                            cycle_variant = true;
+                        }
+                        if code == Key::F3 {
+                            self.hud.toggle();
+                            redraw.invalidate();
+                        }
+                        if code == Key::F12 {
+                            self.request_clip(if shift {
+                                ClipAction::Replay
+                            } else {
+                                ClipAction::Screenshot
+                            });
+                        }
                     }
+                    Event::Resized { .. } | Event::GainedFocus | Event::MouseEntered => {
+                        // Likely shown again, so don't wait for the next visibility check
+                        self.visibility = None;
+                        redraw.invalidate()
+                    }
+                    Event::MouseButtonPressed {
+                        button: mouse::Button::Left,
+                        x,
+                        y,
+                    } if self.is_editing() => {
+                        let hand = self.avatar.hand_position();
+                        self.editor.press(self.canvas_point(x, y), (hand.x, hand.y));
+                    }
+                    Event::MouseMoved { x, y } => {
+                        if self.editor.drag_to(self.canvas_point(x, y)) {
+                            self.apply_calibration();
+                            redraw.invalidate();
+                        }
+                    }
+                    Event::MouseButtonReleased {
+                        button: mouse::Button::Left,
+                        ..
+                    } => self.editor.release(),
                     _ => {}
                 }
             }
 
-            if reload_config {
+            if reload_config || cycle_variant {
                 let old_config = self.avatar.config();
-                let new_config = Config::new(&old_config.config_path, &old_config.images_path);
+                let old_overlay = old_config.overlay.clone();
+                let mut new_config = if reload_config {
+                    let mut new_config =
+                        Config::new(&old_config.config_path, &old_config.images_path);
+                    // Stay on the variant picked with the hotkey while it still exists
+                    if let Some(variant) = &old_config.variant {
+                        if new_config.variants.contains_key(variant) {
+                            new_config.variant = Some(variant.clone());
+                        }
+                    }
+                    new_config
+                } else {
+                    old_config.clone()
+                };
+                if cycle_variant {
+                    new_config.variant = new_config.next_variant();
+                    info!(
+                        "Switching to variant {}",
+                        new_config.variant.as_deref().unwrap_or("<none>")
+                    );
+                }
                 events.update_config(&new_config);
                 background_color = new_config.background.clone();
-                self.avatar.update_config(new_config)?;
+                let window = &mut self.window;
+                let queued = &mut self.queued_events;
+                let loading_color: Color = background_color.clone().into();
+                self.avatar.update_config(new_config, |done, total| {
+                    draw_loading_screen(window, queued, loading_color, done, total)
+                })?;
+                if reload_config {
+                    self.editor = CalibrationEditor::new(self.avatar.config());
+                    self.shaders = ShaderChain::load(self.avatar.config());
+                }
+                self.apply_overlay(&old_overlay);
+                pacer.set_rates(&self.avatar.config().render);
+                redraw.invalidate();
                 reload_config = false;
+                cycle_variant = false;
             }
 
-            self.window.clear(background_color.clone().into());
-            self.avatar.draw(&mut self.window, keyboard, mouse)?;
-            self.window.display();
+            if pacer.frame_due(now) {
+                let background = background_color.clone().into();
+                self.draw_frame(keyboard, mouse, background, &mut redraw, now)?;
+            }
+            let background = background_color.clone().into();
+            capture_outputs(&mut self.outputs, &mut self.avatar, keyboard, mouse, background, now);
+            let now = Instant::now();
+            let wait = outputs_wait(&self.outputs, now, pacer.wait(now));
+            thread::sleep(wait);
         }
         Ok(())
     }
 }
 
-impl<'a> Default for PtuberWindow<'a> {
+impl Default for PtuberWindow {
     fn default() -> Self {
         let default_config: Config = Default::default();
         Self::new(
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use sfml::system::Vector2i;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{ConfigError, TomlError};
+use crate::Monitor;
+
+const WINDOW_STATE_NAME: &str = "window_state.toml";
+
+/// Where the window was when it was last closed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowState {
+    /// Monitor the window was on, `x` and `y` are relative to it when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl WindowState {
+    /// The state is kept next to the config so every skin remembers its own spot
+    pub fn path(config_path: &Path) -> PathBuf {
+        config_path.with_file_name(WINDOW_STATE_NAME)
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let state = fs::read_to_string(path).ok()?;
+        toml::from_str(&state).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let state = toml::to_string(self).map_err(TomlError::from)?;
+        fs::write(path, state)?;
+        Ok(())
+    }
+
+    pub fn capture(position: Vector2i, monitors: &[Monitor]) -> Self {
+        match monitors.iter().find(|monitor| monitor.contains(position)) {
+            Some(monitor) => Self {
+                monitor: Some(monitor.name.clone()),
+                x: position.x - monitor.position.x,
+                y: position.y - monitor.position.y,
+            },
+            None => Self {
+                monitor: None,
+                x: position.x,
+                y: position.y,
+            },
+        }
+    }
+
+    /// Desktop position to open the window at, `None` when its monitor is gone
+    pub fn restore(&self, monitors: &[Monitor]) -> Option<Vector2i> {
+        let position = Vector2i::new(self.x, self.y);
+        match &self.monitor {
+            Some(name) => monitors
+                .iter()
+                .find(|monitor| &monitor.name == name)
+                .map(|monitor| monitor.position + position),
+            None if monitors.is_empty() => Some(position),
+            None => monitors
+                .iter()
+                .any(|monitor| monitor.contains(position))
+                .then_some(position),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                name: "DP-1".to_string(),
+                position: Vector2i::new(0, 0),
+                size: Vector2i::new(1920, 1080),
+            },
+            Monitor {
+                name: "HDMI-1".to_string(),
+                position: Vector2i::new(1920, 0),
+                size: Vector2i::new(2560, 1440),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_capture_is_relative_to_monitor() {
+        let state = WindowState::capture(Vector2i::new(2000, 50), &monitors());
+        assert_eq!(Some("HDMI-1".to_string()), state.monitor);
+        assert_eq!((80, 50), (state.x, state.y));
+    }
+
+    #[test]
+    fn test_restore_follows_moved_monitor() {
+        let state = WindowState::capture(Vector2i::new(2000, 50), &monitors());
+        let mut moved = monitors();
+        moved[1].position = Vector2i::new(-2560, 0);
+        assert_eq!(Some(Vector2i::new(-2480, 50)), state.restore(&moved));
+    }
+
+    #[test]
+    fn test_restore_missing_monitor() {
+        let state = WindowState::capture(Vector2i::new(2000, 50), &monitors());
+        assert_eq!(None, state.restore(&monitors()[..1]));
+    }
+
+    #[test]
+    fn test_restore_without_monitor_info() {
+        let state = WindowState::capture(Vector2i::new(2000, 50), &[]);
+        assert_eq!(Some(Vector2i::new(2000, 50)), state.restore(&[]));
+    }
+}
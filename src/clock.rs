@@ -0,0 +1,39 @@
+use std::cell::Cell;
+use std::time::Instant;
+
+thread_local! {
+    /// Time that `now` reports instead of the real time while it is set.
+    ///
+    /// Kept per thread, so a frozen clock never leaks into other threads, tests included.
+    static FROZEN: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// The current time, as animations and input timing on this thread see it
+pub fn now() -> Instant {
+    FROZEN.with(Cell::get).unwrap_or_else(Instant::now)
+}
+
+/// Stops the clock of the calling thread at `time`, or lets it run again with `None`.
+///
+/// Offline rendering steps the clock frame by frame to draw faster than real time.
+pub fn set_frozen(time: Option<Instant>) {
+    FROZEN.with(|frozen| frozen.set(time));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_frozen_clock() {
+        let time = Instant::now() + Duration::from_secs(60);
+        set_frozen(Some(time));
+        assert_eq!(time, now());
+        // Other threads keep the real time
+        assert!(thread::spawn(now).join().unwrap() < time);
+        set_frozen(None);
+        assert!(now() < time);
+    }
+}
@@ -3,14 +3,17 @@ use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use sfml::system::Vector2;
 use sfml::window::VideoMode;
-use std::fs::OpenOptions;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
 use std::io::prelude::*;
-use std::io::BufReader;
-use std::io::BufWriter;
+use std::io::{self, BufReader};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use toml;
+use toml_edit::{Document, Item, TableLike};
 
-use crate::{default_config, default_skin_dir, GamepadMouseStick};
+use super::{ConfigError, TomlError};
+use crate::{default_config, default_skin_dir, GamepadMouseStick, MAX_FRAMERATE};
 use sfml::graphics::Color as SfmlColor;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -20,14 +23,40 @@ pub struct Config {
     #[serde(skip)]
     pub images_path: PathBuf,
     pub debug: bool,
+    /// Only used to pick the default `layers` when none are given
     pub avatar_below_arm: bool,
+    /// Name of the active entry in `variants`, the plain skin when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
     pub gamepad: GamepadSettings,
     pub window: WindowDimensions,
+    #[serde(default)]
+    pub overlay: OverlaySettings,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub smoothing: SmoothingSettings,
+    #[serde(default)]
+    pub stream: StreamSettings,
+    #[serde(default)]
+    pub clips: ClipSettings,
     pub background: Color,
     #[serde(with = "VectorDef")]
     pub mouse_scale: Vector2<IntOrFloat>,
     pub anchors: Anchors,
     pub mouse_mark: MouseMark,
+    #[serde(default)]
+    pub ik_arm: IkArmSettings,
+    #[serde(default)]
+    pub parts: HashMap<String, PartSettings>,
+    #[serde(default)]
+    pub variants: BTreeMap<String, Variant>,
+    /// Drawing order, bottom first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<Layer>,
+    /// Post-processing applied to the composed scene, first pass first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shaders: Vec<ShaderPass>,
 }
 
 impl Config {
@@ -35,6 +64,120 @@ impl Config {
         Self::load_config_from_file(config_path, images_path)
     }
 
+    /// Reads the config at `config_path` without creating it, defaults when there is none.
+    ///
+    /// Unlike `new`, a config that doesn't parse is an error, so it is never written over.
+    pub fn load(config_path: &Path, images_path: &Path) -> Result<Self, ConfigError> {
+        let mut config = match fs::read_to_string(config_path) {
+            Ok(text) if text.trim().is_empty() => Self::default(),
+            Ok(text) => toml::from_str(&text).map_err(TomlError::from)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Self::set_paths_in_config(&mut config, config_path, images_path);
+        Ok(config)
+    }
+
+    /// Settings for the part whose file name (without extension) is `name`
+    pub fn part_settings(&self, name: &str) -> PartSettings {
+        self.parts.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Recoloring the active variant applies to the part named `name`
+    pub fn part_recolor(&self, name: &str) -> Option<Recolor> {
+        self.variant
+            .as_ref()
+            .and_then(|variant| self.variants.get(variant))
+            .and_then(|variant| variant.parts.get(name))
+            .cloned()
+    }
+
+    /// The configured layers, or the classic order picked by `avatar_below_arm`
+    pub fn layers(&self) -> Vec<Layer> {
+        if !self.layers.is_empty() {
+            return self.layers.clone();
+        }
+        let middle = if self.avatar_below_arm {
+            [RigPart::Avatar, RigPart::Mouse, RigPart::RightArm]
+        } else {
+            [RigPart::Mouse, RigPart::RightArm, RigPart::Avatar]
+        };
+        std::iter::once(RigPart::Background)
+            .chain(middle)
+            .chain(std::iter::once(RigPart::LeftArm))
+            .map(Layer::part)
+            .collect()
+    }
+
+    /// Images drawn by `Image` layers
+    pub fn layer_images(&self) -> Vec<String> {
+        self.layers
+            .iter()
+            .filter_map(|layer| match &layer.source {
+                LayerSource::Image { image } => Some(image.clone()),
+                LayerSource::Part { .. } => None,
+            })
+            .collect()
+    }
+
+    /// The segment images of the two-bone mouse arm, when it is used
+    pub fn ik_arm_images(&self) -> Vec<String> {
+        if self.ik_arm.enabled {
+            vec![self.ik_arm.upper_arm.clone(), self.ik_arm.forearm.clone()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The variant after the active one, going back to the plain skin after the last
+    pub fn next_variant(&self) -> Option<String> {
+        let mut names = self.variants.keys();
+        match &self.variant {
+            None => names.next().cloned(),
+            Some(current) => names
+                .skip_while(|name| *name != current)
+                .nth(1)
+                .cloned(),
+        }
+    }
+
+    pub fn save_to_file(&self, config_path: &Path) -> Result<(), ConfigError> {
+        // Going through a Value puts plain values before tables, which TOML requires
+        let value = toml::Value::try_from(self).map_err(TomlError::from)?;
+        let config_string = toml::to_string(&value).map_err(TomlError::from)?;
+        fs::write(config_path, config_string)?;
+        Ok(())
+    }
+
+    /// Writes `anchors` and `mouse_mark` into the config file at `config_path`, leaving the
+    /// rest of the file, comments included, as it was on disk
+    pub fn save_calibration(&self, config_path: &Path) -> Result<(), ConfigError> {
+        let existing = match fs::read_to_string(config_path) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        fs::write(config_path, self.with_calibration(&existing)?)?;
+        Ok(())
+    }
+
+    /// `existing` config text with this config's calibration put in
+    fn with_calibration(&self, existing: &str) -> Result<String, TomlError> {
+        #[derive(Serialize)]
+        struct Calibration<'a> {
+            anchors: &'a Anchors,
+            mouse_mark: &'a MouseMark,
+        }
+        let calibration = Calibration {
+            anchors: &self.anchors,
+            mouse_mark: &self.mouse_mark,
+        };
+        let calibration: Document = toml::to_string(&calibration)?.parse()?;
+        let mut document: Document = existing.parse()?;
+        merge_table(document.as_table_mut(), calibration.as_table());
+        Ok(document.to_string())
+    }
+
     fn set_paths_in_config(config: &mut Self, config_path: &Path, images_path: &Path) {
         config.config_path = PathBuf::from(config_path);
         config.images_path = PathBuf::from(images_path);
@@ -59,10 +202,9 @@ impl Config {
                         ..Default::default()
                     };
                     Self::set_paths_in_config(&mut default, config_path, images_path);
-                    let mut config_writer = BufWriter::new(&file);
-                    let _bytes_written = config_writer
-                        .write(toml::to_string(&default).unwrap().as_bytes())
-                        .unwrap_or(0);
+                    if let Err(err) = default.save_to_file(config_path) {
+                        warn!("Could not write the default config: {:?}", err);
+                    }
                     default
                 } else {
                     let mut config = toml::from_str(&config_string)
@@ -81,6 +223,30 @@ impl Config {
     }
 }
 
+/// Copies every value in `from` into `into`, keeping the comments and layout around `into`'s
+fn merge_table(into: &mut dyn TableLike, from: &dyn TableLike) {
+    for (key, value) in from.iter() {
+        match into.get_mut(key) {
+            Some(existing) => merge_item(existing, value),
+            None => {
+                into.insert(key, value.clone());
+            }
+        }
+    }
+}
+
+fn merge_item(into: &mut Item, from: &Item) {
+    if let (Some(into), Some(from)) = (into.as_table_like_mut(), from.as_table_like()) {
+        merge_table(into, from);
+        return;
+    }
+    let decor = into.as_value().map(|value| value.decor().clone());
+    *into = from.clone();
+    if let (Some(decor), Some(value)) = (decor, into.as_value_mut()) {
+        *value.decor_mut() = decor;
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         let images_path = PathBuf::from(default_skin_dir());
@@ -91,8 +257,19 @@ impl Default for Config {
         let avatar_below_arm = false;
         let anchors = Default::default();
         let mouse_mark = Default::default();
+        let ik_arm = Default::default();
         let mouse_scale = Vector2::new(1.into(), 1.into());
         let gamepad = Default::default();
+        let overlay = Default::default();
+        let render = Default::default();
+        let smoothing = Default::default();
+        let stream = Default::default();
+        let clips = Default::default();
+        let parts = Default::default();
+        let variant = None;
+        let variants = Default::default();
+        let layers = Vec::new();
+        let shaders = Vec::new();
         Self {
             config_path,
             images_path,
@@ -102,12 +279,224 @@ impl Default for Config {
             avatar_below_arm,
             anchors,
             mouse_mark,
+            ik_arm,
             mouse_scale,
-            gamepad
+            gamepad,
+            overlay,
+            render,
+            smoothing,
+            stream,
+            clips,
+            parts,
+            variant,
+            variants,
+            layers,
+            shaders,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PartSettings {
+    #[serde(default)]
+    pub playback: Playback,
+    /// Where the image's top left corner sits on the skin canvas
+    #[serde(default = "zero_vector", with = "VectorDef")]
+    pub offset: Vector2<IntOrFloat>,
+    #[serde(default = "unit_vector", with = "VectorDef")]
+    pub scale: Vector2<IntOrFloat>,
+    /// Size of the canvas the image was cut from, defaults to the scaled image size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canvas: Option<CanvasSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outline: Option<Outline>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<Shadow>,
+}
+
+impl Default for PartSettings {
+    fn default() -> Self {
+        Self {
+            playback: Default::default(),
+            offset: zero_vector(),
+            scale: unit_vector(),
+            canvas: None,
+            outline: None,
+            shadow: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CanvasSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Outline traced around the visible pixels of a part, like a sticker border
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Outline {
+    #[serde(default = "outline_color")]
+    pub color: Color,
+    /// Thickness in image pixels
+    pub width: u32,
+}
+
+/// Soft shadow cast by a part, drawn behind it and its outline
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Shadow {
+    #[serde(default)]
+    pub color: Color,
+    /// Shift in image pixels
+    #[serde(default = "shadow_offset", with = "VectorDef")]
+    pub offset: Vector2<IntOrFloat>,
+    /// Blur strength in image pixels, 0 for a hard shadow
+    #[serde(default = "shadow_blur")]
+    pub blur: f32,
+    #[serde(default = "shadow_opacity")]
+    pub opacity: f32,
+}
+
+/// Parts of the rig that can be placed in the layer stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RigPart {
+    Background,
+    Avatar,
+    /// The mouse, or whatever device the hand holds
+    Mouse,
+    RightArm,
+    LeftArm,
+}
+
+/// What a layer draws
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LayerSource {
+    Part { part: RigPart },
+    /// Any other image from the skin directory, drawn at the canvas origin
+    Image { image: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Add,
+    Multiply,
+}
+
+/// One entry of the drawing order, moved and blended as a whole
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Layer {
+    #[serde(flatten)]
+    pub source: LayerSource,
+    /// Degrees, clockwise around `origin`
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub blend: BlendMode,
+    #[serde(default = "zero_vector", with = "VectorDef")]
+    pub offset: Vector2<IntOrFloat>,
+    /// Scale, around `origin`
+    #[serde(default = "unit_vector", with = "VectorDef")]
+    pub scale: Vector2<IntOrFloat>,
+    /// Point on the canvas the layer is rotated and scaled around
+    #[serde(default = "zero_vector", with = "VectorDef")]
+    pub origin: Vector2<IntOrFloat>,
+    /// Input expression, the layer is only drawn while it holds, e.g. `key:Space && !mouse:Left`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+impl Layer {
+    pub fn part(part: RigPart) -> Self {
+        Self {
+            source: LayerSource::Part { part },
+            rotation: 0.0,
+            opacity: default_opacity(),
+            blend: Default::default(),
+            offset: zero_vector(),
+            scale: unit_vector(),
+            origin: zero_vector(),
+            when: None,
         }
     }
 }
 
+fn default_opacity() -> f32 {
+    1.0
+}
+
+/// A named colorway of the skin
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Variant {
+    #[serde(default)]
+    pub parts: HashMap<String, Recolor>,
+}
+
+/// Color changes applied to a part's pixels, palette first, then hue shift, then tint
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Recolor {
+    /// Exact colors to replace, these pixels skip the hue shift and tint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palette: Vec<PaletteEntry>,
+    /// Degrees to rotate the hue by
+    #[serde(default)]
+    pub hue_shift: f32,
+    /// Color every pixel is multiplied with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tint: Option<Color>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PaletteEntry {
+    pub from: Color,
+    pub to: Color,
+}
+
+fn outline_color() -> Color {
+    Color {
+        red: 255,
+        green: 255,
+        blue: 255,
+        alpha: 255,
+    }
+}
+
+fn shadow_offset() -> Vector2<IntOrFloat> {
+    Vector2::new(6.into(), 6.into())
+}
+
+fn shadow_blur() -> f32 {
+    4.0
+}
+
+fn shadow_opacity() -> f32 {
+    0.5
+}
+
+fn zero_vector() -> Vector2<IntOrFloat> {
+    Vector2::new(0.into(), 0.into())
+}
+
+fn unit_vector() -> Vector2<IntOrFloat> {
+    Vector2::new(1.into(), 1.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Playback {
+    /// Play the animation forever
+    #[default]
+    Loop,
+    /// Play the animation once each time the part is shown, then hold the last frame
+    Once,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GamepadSettings {
     pub enabled: bool,
@@ -126,13 +515,31 @@ impl Default for GamepadSettings {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WindowDimensions {
+    /// Width of the scene, the window opens at this size times `scale_factor`
     pub width: u32,
     pub height: u32,
+    #[serde(default = "default_true")]
+    pub resizable: bool,
+    /// Scale the scene by whole numbers only and draw textures without smoothing
+    #[serde(default)]
+    pub pixel_art: bool,
+    /// Extra scale for HiDPI screens
+    #[serde(default = "default_scale_factor")]
+    pub scale_factor: f32,
+    /// Let the desktop show through wherever the background color's alpha allows.
+    /// Needs a compositing window manager, and `background.png` is only drawn if the skin has one
+    #[serde(default)]
+    pub transparent: bool,
 }
 
 impl From<WindowDimensions> for VideoMode {
     fn from(value: WindowDimensions) -> Self {
-        VideoMode::new(value.width, value.height, 32)
+        let scale = value.scale_factor.max(0.1);
+        VideoMode::new(
+            (value.width as f32 * scale).round() as u32,
+            (value.height as f32 * scale).round() as u32,
+            32,
+        )
     }
 }
 
@@ -141,10 +548,325 @@ impl Default for WindowDimensions {
         Self {
             width: 612,
             height: 467,
+            resizable: true,
+            pixel_art: false,
+            scale_factor: default_scale_factor(),
+            transparent: false,
+        }
+    }
+}
+
+/// Settings for placing the avatar over other windows, like a game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OverlaySettings {
+    /// Hide the title bar and window frame
+    #[serde(default)]
+    pub borderless: bool,
+    #[serde(default)]
+    pub always_on_top: bool,
+    /// Let clicks go through to the window below
+    #[serde(default)]
+    pub click_through: bool,
+    /// Open the window where, and on the monitor, it was last closed
+    #[serde(default = "default_true")]
+    pub remember_position: bool,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            borderless: false,
+            always_on_top: false,
+            click_through: false,
+            remember_position: true,
+        }
+    }
+}
+
+/// How often the scene is drawn and input is read
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderSettings {
+    /// Highest number of frames drawn per second
+    #[serde(default = "default_framerate")]
+    pub framerate: u32,
+    /// Times per second keyboard, mouse and gamepad are read
+    #[serde(default = "default_framerate")]
+    pub poll_rate: u32,
+    /// Skip frames when neither input nor animations changed
+    #[serde(default = "default_true")]
+    pub on_change: bool,
+    /// Stop drawing while the window is minimized or hidden
+    #[serde(default = "default_true")]
+    pub pause_hidden: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            framerate: default_framerate(),
+            poll_rate: default_framerate(),
+            on_change: true,
+            pause_hidden: true,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.framerate.max(1)
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.poll_rate.max(1)
+    }
+}
+
+/// How the hand follows the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandFilter {
+    /// The hand is wherever the cursor is
+    #[default]
+    None,
+    /// Closes a fixed share of the distance every `half_life`
+    Exponential,
+    /// A critically damped spring, quick without overshooting
+    Spring,
+    /// Smooths slow movements strongly and fast ones barely, against jitter without lag
+    OneEuro,
+}
+
+/// Filtering of the cursor position the hand follows, in screen widths and seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SmoothingSettings {
+    #[serde(default)]
+    pub filter: HandFilter,
+    /// `exponential`: seconds to cover half the distance to the cursor
+    #[serde(default = "default_half_life")]
+    pub half_life: f32,
+    /// `spring`: stiffness, in radians per second
+    #[serde(default = "default_spring_frequency")]
+    pub frequency: f32,
+    /// `one_euro`: cutoff in Hz while the cursor is still, lower is smoother
+    #[serde(default = "default_cutoff")]
+    pub min_cutoff: f32,
+    /// `one_euro`: how much the cutoff rises with speed, higher lags less
+    #[serde(default = "default_beta")]
+    pub beta: f32,
+    /// Cutoff in Hz for the speed estimate used by `one_euro` and `prediction`
+    #[serde(default = "default_cutoff")]
+    pub derivative_cutoff: f32,
+    /// Seconds to extrapolate the hand along its velocity, 0 turns prediction off
+    #[serde(default)]
+    pub prediction: f32,
+}
+
+impl Default for SmoothingSettings {
+    fn default() -> Self {
+        Self {
+            filter: Default::default(),
+            half_life: default_half_life(),
+            frequency: default_spring_frequency(),
+            min_cutoff: default_cutoff(),
+            beta: default_beta(),
+            derivative_cutoff: default_cutoff(),
+            prediction: 0.0,
         }
     }
 }
 
+/// Post-processing shaders that come with ptuber
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinShader {
+    /// Makes the key color transparent and removes its spill
+    ChromaKey,
+    /// Scanlines, curvature and a vignette
+    Crt,
+    /// A glow around the opaque parts of the scene
+    Glow,
+    /// Brightness, contrast, saturation, gamma and tint
+    ColorGrade,
+}
+
+/// Where a shader pass comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ShaderSource {
+    Builtin { builtin: BuiltinShader },
+    /// A GLSL fragment shader in the skin directory
+    File { file: String },
+}
+
+/// A float, or a `vec2` to `vec4` written as a list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UniformValue {
+    Float(f32),
+    Vector(Vec<f32>),
+}
+
+/// One pass of the post-processing chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShaderPass {
+    #[serde(flatten)]
+    pub source: ShaderSource,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Values for the shader's own uniforms, by name
+    #[serde(default)]
+    pub uniforms: BTreeMap<String, UniformValue>,
+}
+
+/// The HTTP server streaming the scene to OBS or a browser
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StreamSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on, `0.0.0.0` to be reachable from other machines
+    #[serde(default = "default_stream_address")]
+    pub address: String,
+    #[serde(default = "default_stream_port")]
+    pub port: u16,
+    /// JPEG quality of the MJPEG stream, from 1 to 100
+    #[serde(default = "default_stream_quality")]
+    pub quality: u8,
+    /// Frames per second sent to each viewer
+    #[serde(default = "default_stream_framerate")]
+    pub framerate: u32,
+    /// Requests answered at once, more are turned away
+    #[serde(default = "default_stream_max_clients")]
+    pub max_clients: usize,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: default_stream_address(),
+            port: default_stream_port(),
+            quality: default_stream_quality(),
+            framerate: default_stream_framerate(),
+            max_clients: default_stream_max_clients(),
+        }
+    }
+}
+
+impl StreamSettings {
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.framerate.max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipFormat {
+    #[default]
+    Gif,
+    Apng,
+}
+
+/// Screenshots and replay clips of the scene
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClipSettings {
+    /// Where screenshots and clips are saved, relative to the config file
+    #[serde(default = "default_clip_folder")]
+    pub folder: PathBuf,
+    /// Length of the replay kept in memory, off with the default 0
+    #[serde(default)]
+    pub replay_seconds: u32,
+    /// Frames per second kept for the replay
+    #[serde(default = "default_replay_framerate")]
+    pub replay_framerate: u32,
+    #[serde(default)]
+    pub replay_format: ClipFormat,
+}
+
+impl Default for ClipSettings {
+    fn default() -> Self {
+        Self {
+            folder: default_clip_folder(),
+            replay_seconds: 0,
+            replay_framerate: default_replay_framerate(),
+            replay_format: Default::default(),
+        }
+    }
+}
+
+impl ClipSettings {
+    /// The folder, resolved next to the config file at `config_path`
+    pub fn folder_path(&self, config_path: &Path) -> PathBuf {
+        config_path
+            .parent()
+            .map(|dir| dir.join(&self.folder))
+            .unwrap_or_else(|| self.folder.clone())
+    }
+
+    pub fn replay_length(&self) -> Duration {
+        Duration::from_secs(self.replay_seconds as u64)
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.replay_framerate.max(1)
+    }
+}
+
+fn default_clip_folder() -> PathBuf {
+    PathBuf::from("clips")
+}
+
+fn default_replay_framerate() -> u32 {
+    15
+}
+
+fn default_stream_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_stream_port() -> u16 {
+    8080
+}
+
+fn default_stream_quality() -> u8 {
+    80
+}
+
+fn default_stream_framerate() -> u32 {
+    30
+}
+
+fn default_stream_max_clients() -> usize {
+    8
+}
+
+fn default_half_life() -> f32 {
+    0.03
+}
+
+fn default_spring_frequency() -> f32 {
+    25.0
+}
+
+fn default_cutoff() -> f32 {
+    1.0
+}
+
+fn default_beta() -> f32 {
+    1.0
+}
+
+fn default_framerate() -> u32 {
+    MAX_FRAMERATE
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_scale_factor() -> f32 {
+    1.0
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(transparent)]
 pub struct IntOrFloat {
@@ -182,7 +904,7 @@ struct VectorDef<S> {
     y: S,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
@@ -242,3 +964,284 @@ impl Default for MouseMark {
         }
     }
 }
+
+/// Which way the elbow bends, turning from the line between shoulder and hand as seen on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BendDirection {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+/// A mouse arm of an upper arm and a forearm joined at the elbow, instead of one stretched
+/// `arm.png`. The shoulder is `anchors.anchor`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IkArmSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_upper_arm_image")]
+    pub upper_arm: String,
+    #[serde(default = "default_forearm_image")]
+    pub forearm: String,
+    /// Shoulder to elbow, in skin pixels
+    #[serde(default = "default_segment_length")]
+    pub upper_length: f32,
+    /// Elbow to hand, in skin pixels
+    #[serde(default = "default_segment_length")]
+    pub forearm_length: f32,
+    #[serde(default)]
+    pub bend: BendDirection,
+    /// Share of the full length the arm stretches to, short of locking straight
+    #[serde(default = "default_max_reach")]
+    pub max_reach: f32,
+    /// Share of the distance past `max_reach` the shoulder leans towards the hand
+    #[serde(default = "default_shoulder_shift")]
+    pub shoulder_shift: f32,
+    /// Farthest the shoulder leans, in skin pixels
+    #[serde(default = "default_max_shoulder_shift")]
+    pub max_shoulder_shift: f32,
+    // Tables go after the plain values, or TOML can't write them
+    /// Point of `upper_arm` at the shoulder, the image hangs straight down from it
+    #[serde(default = "zero_vector", with = "VectorDef")]
+    pub shoulder_joint: Vector2<IntOrFloat>,
+    /// Point of `forearm` at the elbow, the image hangs straight down from it
+    #[serde(default = "zero_vector", with = "VectorDef")]
+    pub elbow_joint: Vector2<IntOrFloat>,
+}
+
+impl Default for IkArmSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            upper_arm: default_upper_arm_image(),
+            forearm: default_forearm_image(),
+            upper_length: default_segment_length(),
+            forearm_length: default_segment_length(),
+            bend: Default::default(),
+            max_reach: default_max_reach(),
+            shoulder_shift: default_shoulder_shift(),
+            max_shoulder_shift: default_max_shoulder_shift(),
+            shoulder_joint: zero_vector(),
+            elbow_joint: zero_vector(),
+        }
+    }
+}
+
+fn default_upper_arm_image() -> String {
+    "upper_arm.png".to_string()
+}
+
+fn default_forearm_image() -> String {
+    "forearm.png".to_string()
+}
+
+fn default_segment_length() -> f32 {
+    100.0
+}
+
+fn default_max_reach() -> f32 {
+    0.95
+}
+
+fn default_shoulder_shift() -> f32 {
+    0.3
+}
+
+fn default_max_shoulder_shift() -> f32 {
+    15.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_variants(names: &[&str]) -> Config {
+        let mut config = Config::default();
+        for name in names {
+            config.variants.insert(name.to_string(), Variant::default());
+        }
+        config
+    }
+
+    #[test]
+    fn test_next_variant_cycles_back_to_plain_skin() {
+        let mut config = config_with_variants(&["winter", "autumn"]);
+        config.variant = config.next_variant();
+        assert_eq!(Some("autumn".to_string()), config.variant);
+        config.variant = config.next_variant();
+        assert_eq!(Some("winter".to_string()), config.variant);
+        assert_eq!(None, config.next_variant());
+    }
+
+    #[test]
+    fn test_next_variant_without_variants() {
+        let config = config_with_variants(&[]);
+        assert_eq!(None, config.next_variant());
+    }
+
+    #[test]
+    fn test_default_config_round_trips() {
+        let config = Config::default();
+        let written = toml::to_string(&config).unwrap();
+        let read: Config = toml::from_str(&written).unwrap();
+        assert_eq!(config.smoothing, read.smoothing);
+        assert_eq!(config.ik_arm.bend, read.ik_arm.bend);
+        assert_eq!(config.ik_arm.upper_length, read.ik_arm.upper_length);
+        let joint = |config: &Config| -> (f32, f32) {
+            let joint = &config.ik_arm.elbow_joint;
+            (joint.x.into(), joint.y.into())
+        };
+        assert_eq!(joint(&config), joint(&read));
+        assert_eq!(config.stream.max_clients, read.stream.max_clients);
+    }
+
+    #[test]
+    fn test_calibration_keeps_rest_of_file() {
+        let existing = "# my skin\n\
+                        debug = true\n\
+                        [anchors]\n\
+                        anchor = { x = 1, y = 2 } # shoulder\n\
+                        arm_offset = { x = 3, y = 4 }\n";
+        let mut config = Config::default();
+        config.debug = false;
+        config.variant = Some("winter".to_string());
+        config.anchors.anchor = Vector2::new(10.into(), 20.into());
+        let saved = config.with_calibration(existing).unwrap();
+        assert!(saved.starts_with("# my skin\ndebug = true\n"), "{}", saved);
+        assert!(saved.contains("anchor = { x = 10, y = 20 } # shoulder"), "{}", saved);
+        assert!(!saved.contains("variant"), "{}", saved);
+
+        let saved: toml::Value = toml::from_str(&saved).unwrap();
+        assert_eq!(Some(15), saved["mouse_mark"]["rotation"].as_integer());
+        assert_eq!(Some(67), saved["anchors"]["arm_offset"]["x"].as_integer());
+    }
+
+    #[test]
+    fn test_load_neither_creates_nor_hides_broken_config() {
+        let dir = std::env::temp_dir().join(format!("ptuber_config_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let _ = fs::remove_file(&path);
+        let config = Config::load(&path, &dir).unwrap();
+        assert_eq!(path, config.config_path);
+        assert!(!path.exists());
+
+        fs::write(&path, "debug = tru\n").unwrap();
+        assert!(Config::load(&path, &dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn layer_parts(config: &Config) -> Vec<LayerSource> {
+        config.layers().into_iter().map(|layer| layer.source).collect()
+    }
+
+    #[test]
+    fn test_default_layers_follow_avatar_below_arm() {
+        let mut config = Config::default();
+        let part = |part| LayerSource::Part { part };
+        assert_eq!(
+            vec![
+                part(RigPart::Background),
+                part(RigPart::Mouse),
+                part(RigPart::RightArm),
+                part(RigPart::Avatar),
+                part(RigPart::LeftArm),
+            ],
+            layer_parts(&config)
+        );
+        config.avatar_below_arm = true;
+        assert_eq!(part(RigPart::Avatar), layer_parts(&config)[1]);
+    }
+
+    #[test]
+    fn test_parse_layers() {
+        let layers: Config = toml::from_str(
+            r#"
+            debug = false
+            avatar_below_arm = false
+            [gamepad]
+            enabled = false
+            gamepad_id = 0
+            mouse_move_joystick = "Left"
+            [window]
+            width = 612
+            height = 467
+            [background]
+            red = 0
+            green = 0
+            blue = 0
+            alpha = 255
+            [mouse_scale]
+            x = 1
+            y = 1
+            [anchors]
+            anchor = { x = 0, y = 0 }
+            arm_offset = { x = 0, y = 0 }
+            [mouse_mark]
+            rotation = 0
+            position = { x = 0, y = 0 }
+            size = { x = 1, y = 1 }
+            [[layers]]
+            part = "left_arm"
+            [[layers]]
+            image = "sparkles.png"
+            blend = "add"
+            opacity = 0.5
+            when = "key:Space && !mouse:Left"
+            "#,
+        )
+        .unwrap();
+        let layers = layers.layers();
+        assert_eq!(LayerSource::Part { part: RigPart::LeftArm }, layers[0].source);
+        assert_eq!(
+            LayerSource::Image {
+                image: "sparkles.png".to_string()
+            },
+            layers[1].source
+        );
+        assert_eq!(BlendMode::Add, layers[1].blend);
+        assert_eq!(0.5, layers[1].opacity);
+        assert_eq!(None, layers[0].when);
+        assert_eq!(Some("key:Space && !mouse:Left"), layers[1].when.as_deref());
+    }
+
+    #[test]
+    fn test_parse_shader_passes() {
+        let builtin: ShaderPass = toml::from_str(
+            r#"
+            builtin = "color_grade"
+            uniforms = { saturation = 0, tint = [1, 0.5, 0.5] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            ShaderSource::Builtin {
+                builtin: BuiltinShader::ColorGrade
+            },
+            builtin.source
+        );
+        assert!(builtin.enabled);
+        assert_eq!(Some(&UniformValue::Float(0.0)), builtin.uniforms.get("saturation"));
+        assert_eq!(
+            Some(&UniformValue::Vector(vec![1.0, 0.5, 0.5])),
+            builtin.uniforms.get("tint")
+        );
+
+        let file: ShaderPass = toml::from_str("file = \"wobble.frag\"\nenabled = false").unwrap();
+        assert_eq!(
+            ShaderSource::File {
+                file: "wobble.frag".to_string()
+            },
+            file.source
+        );
+        assert!(!file.enabled);
+    }
+
+    #[test]
+    fn test_unknown_variant_restarts_cycle() {
+        let mut config = config_with_variants(&["winter"]);
+        config.variant = Some("summer".to_string());
+        assert_eq!(None, config.next_variant());
+    }
+}
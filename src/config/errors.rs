@@ -16,4 +16,5 @@ pub enum TomlError {
     Deserialize(#[from] toml::de::Error),
     Serialize(#[from] toml::ser::Error),
     DateTimeParse(#[from] toml::value::DatetimeParseError),
+    Edit(#[from] toml_edit::TomlError),
 }
@@ -1,5 +1,11 @@
 mod config_impl;
 mod errors;
 
-pub use self::config_impl::{Anchors, Color, Config, WindowDimensions};
-pub(crate) use self::errors::ConfigError;
+pub use self::config_impl::{
+    Anchors, BendDirection, BlendMode, BuiltinShader, CanvasSize, ClipFormat, ClipSettings,
+    Color, Config, HandFilter, IkArmSettings, IntOrFloat, Layer, LayerSource, Outline,
+    OverlaySettings, PaletteEntry, PartSettings, Playback, Recolor, RenderSettings, RigPart,
+    Shadow, ShaderPass, ShaderSource, SmoothingSettings, StreamSettings, UniformValue, Variant,
+    WindowDimensions,
+};
+pub(crate) use self::errors::{ConfigError, TomlError};
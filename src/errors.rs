@@ -1,6 +1,10 @@
 use super::UtilError;
 use crate::avatar::SfmlError;
 use crate::config::ConfigError;
+use crate::render::RenderError;
+use crate::session::SessionError;
+use crate::skin::SkinError;
+use crate::stream::StreamError;
 use crate::WindowFinderError;
 use std::result;
 use thiserror::Error;
@@ -19,6 +23,14 @@ pub enum PTuberError {
     AssetLoad,
     #[error("util error")]
     UtilError(#[from] UtilError),
+    #[error("skin error")]
+    Skin(#[from] SkinError),
+    #[error("session error")]
+    Session(#[from] SessionError),
+    #[error("render error")]
+    Render(#[from] RenderError),
+    #[error("stream error")]
+    Stream(#[from] StreamError),
 }
 
 pub type Result<T> = result::Result<T, PTuberError>;
@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ExpressionError {
+    #[error("unexpected {found:?} at {position}, expected {expected}")]
+    Unexpected {
+        position: usize,
+        found: String,
+        expected: &'static str,
+    },
+    #[error("unknown input {0:?}, expected key:, mouse:, button:, device or idle")]
+    UnknownInput(String),
+    #[error("unknown device {0:?}, expected mouse or gamepad")]
+    UnknownDevice(String),
+    #[error("invalid duration {0:?}, expected a number with ms, s or m")]
+    InvalidDuration(String),
+}
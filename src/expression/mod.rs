@@ -0,0 +1,5 @@
+mod errors;
+mod parser;
+
+pub(crate) use self::errors::ExpressionError;
+pub(crate) use self::parser::{Comparison, DeviceKind, Expression, InputState};
@@ -0,0 +1,425 @@
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+use std::time::Duration;
+
+use super::ExpressionError;
+
+/// Kind of device the hand is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Mouse,
+    Gamepad,
+}
+
+/// Input state an expression is evaluated against. Names are lowercase.
+pub trait InputState {
+    fn key_held(&self, name: &str) -> bool;
+    fn mouse_held(&self, name: &str) -> bool;
+    fn button_held(&self, name: &str) -> bool;
+    fn device(&self) -> DeviceKind;
+    /// Time since the last input of any kind
+    fn idle(&self) -> Duration;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn compare(&self, left: Duration, right: Duration) -> bool {
+        match self {
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// A condition over input state, like `key:Space && !mouse:Left` or `idle > 30s`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Constant(bool),
+    /// `key:NAME`, a keyboard key is held
+    Key(String),
+    /// `mouse:NAME`, a mouse button is held
+    Mouse(String),
+    /// `button:NAME`, a gamepad button is held
+    Button(String),
+    /// `device == NAME`, the hand is on this device
+    Device(DeviceKind),
+    /// `idle > DURATION`, compares the time since the last input
+    Idle(Comparison, Duration),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn evaluate(&self, input: &impl InputState) -> bool {
+        match self {
+            Self::Constant(value) => *value,
+            Self::Key(name) => input.key_held(name),
+            Self::Mouse(name) => input.mouse_held(name),
+            Self::Button(name) => input.button_held(name),
+            Self::Device(device) => input.device() == *device,
+            Self::Idle(comparison, duration) => comparison.compare(input.idle(), *duration),
+            Self::Not(inner) => !inner.evaluate(input),
+            Self::And(left, right) => left.evaluate(input) && right.evaluate(input),
+            Self::Or(left, right) => left.evaluate(input) || right.evaluate(input),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(source)?;
+        let expression = parser.or()?;
+        parser.expect(Token::End, "end of expression")?;
+        Ok(expression)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Equal,
+    NotEqual,
+    Compare(Comparison),
+    Word(String),
+    End,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | ':' | '.')
+}
+
+/// Consumes the next character if it is `next`
+fn followed_by(chars: &mut Peekable<CharIndices>, next: char) -> bool {
+    chars.next_if(|(_, c)| *c == next).is_some()
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if followed_by(&mut chars, '&') => Token::And,
+            '|' if followed_by(&mut chars, '|') => Token::Or,
+            '=' if followed_by(&mut chars, '=') => Token::Equal,
+            '!' if followed_by(&mut chars, '=') => Token::NotEqual,
+            '!' => Token::Not,
+            '<' if followed_by(&mut chars, '=') => Token::Compare(Comparison::LessOrEqual),
+            '<' => Token::Compare(Comparison::Less),
+            '>' if followed_by(&mut chars, '=') => Token::Compare(Comparison::GreaterOrEqual),
+            '>' => Token::Compare(Comparison::Greater),
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => {
+                return Err(ExpressionError::Unexpected {
+                    position,
+                    found: c.to_string(),
+                    expected: "an input, operator or parenthesis",
+                })
+            }
+        };
+        tokens.push((position, token));
+    }
+    tokens.push((source.len(), Token::End));
+    Ok(tokens)
+}
+
+fn parse_duration(word: &str) -> Result<Duration, ExpressionError> {
+    let invalid = || ExpressionError::InvalidDuration(word.to_string());
+    let split = word
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(word.len());
+    let (number, unit) = word.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Recursive descent over `or := and ("||" and)*`, `and := unary ("&&" unary)*`,
+/// `unary := "!" unary | "(" or ")" | input`
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, ExpressionError> {
+        Ok(Self {
+            tokens: tokenize(source)?,
+            index: 0,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].1
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.index].clone();
+        if token.1 != Token::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn unexpected(position: usize, token: &Token, expected: &'static str) -> ExpressionError {
+        let found = match token {
+            Token::Word(word) => word.clone(),
+            Token::End => "end of expression".to_string(),
+            token => format!("{:?}", token),
+        };
+        ExpressionError::Unexpected {
+            position,
+            found,
+            expected,
+        }
+    }
+
+    fn expect(&mut self, expected: Token, description: &'static str) -> Result<(), ExpressionError> {
+        let (position, token) = self.next();
+        if token == expected {
+            Ok(())
+        } else {
+            Err(Self::unexpected(position, &token, description))
+        }
+    }
+
+    fn word(&mut self, description: &'static str) -> Result<String, ExpressionError> {
+        match self.next() {
+            (_, Token::Word(word)) => Ok(word),
+            (position, token) => Err(Self::unexpected(position, &token, description)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.and()?;
+        while *self.peek() == Token::Or {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.unary()?;
+        while *self.peek() == Token::And {
+            self.next();
+            expression = Expression::And(Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.next() {
+            (_, Token::Not) => Ok(Expression::Not(Box::new(self.unary()?))),
+            (_, Token::Open) => {
+                let expression = self.or()?;
+                self.expect(Token::Close, "')'")?;
+                Ok(expression)
+            }
+            (_, Token::Word(word)) => self.input(word),
+            (position, token) => Err(Self::unexpected(position, &token, "an input")),
+        }
+    }
+
+    fn input(&mut self, word: String) -> Result<Expression, ExpressionError> {
+        if let Some((kind, name)) = word.split_once(':') {
+            let name = name.to_lowercase();
+            return match kind {
+                "key" => Ok(Expression::Key(name)),
+                "mouse" => Ok(Expression::Mouse(name)),
+                "button" => Ok(Expression::Button(name)),
+                _ => Err(ExpressionError::UnknownInput(word)),
+            };
+        }
+        match word.as_str() {
+            "true" => Ok(Expression::Constant(true)),
+            "false" => Ok(Expression::Constant(false)),
+            "device" => {
+                let negate = match self.next() {
+                    (_, Token::Equal) => false,
+                    (_, Token::NotEqual) => true,
+                    (position, token) => {
+                        return Err(Self::unexpected(position, &token, "'==' or '!='"))
+                    }
+                };
+                let name = self.word("a device name")?;
+                let device = match name.to_lowercase().as_str() {
+                    "mouse" => DeviceKind::Mouse,
+                    "gamepad" => DeviceKind::Gamepad,
+                    _ => return Err(ExpressionError::UnknownDevice(name)),
+                };
+                let expression = Expression::Device(device);
+                Ok(if negate {
+                    Expression::Not(Box::new(expression))
+                } else {
+                    expression
+                })
+            }
+            "idle" => {
+                let comparison = match self.next() {
+                    (_, Token::Compare(comparison)) => comparison,
+                    (position, token) => {
+                        return Err(Self::unexpected(position, &token, "'<', '<=', '>' or '>='"))
+                    }
+                };
+                let duration = parse_duration(&self.word("a duration")?)?;
+                Ok(Expression::Idle(comparison, duration))
+            }
+            _ => Err(ExpressionError::UnknownInput(word)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestInput {
+        keys: Vec<&'static str>,
+        mouse: Vec<&'static str>,
+        gamepad: bool,
+        idle: Duration,
+    }
+
+    impl InputState for TestInput {
+        fn key_held(&self, name: &str) -> bool {
+            self.keys.contains(&name)
+        }
+        fn mouse_held(&self, name: &str) -> bool {
+            self.mouse.contains(&name)
+        }
+        fn button_held(&self, _name: &str) -> bool {
+            false
+        }
+        fn device(&self) -> DeviceKind {
+            if self.gamepad {
+                DeviceKind::Gamepad
+            } else {
+                DeviceKind::Mouse
+            }
+        }
+        fn idle(&self) -> Duration {
+            self.idle
+        }
+    }
+
+    fn evaluate(source: &str, input: &TestInput) -> bool {
+        source.parse::<Expression>().unwrap().evaluate(input)
+    }
+
+    #[test]
+    fn test_key_and_not_mouse() {
+        let mut input = TestInput {
+            keys: vec!["space"],
+            ..Default::default()
+        };
+        assert!(evaluate("key:Space && !mouse:Left", &input));
+        input.mouse = vec!["left"];
+        assert!(!evaluate("key:Space && !mouse:Left", &input));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let input = TestInput::default();
+        assert!(evaluate("true || false && false", &input));
+        assert!(!evaluate("(true || false) && false", &input));
+        assert!(evaluate("!false && !!true", &input));
+    }
+
+    #[test]
+    fn test_device() {
+        let input = TestInput {
+            gamepad: true,
+            ..Default::default()
+        };
+        assert!(evaluate("device == gamepad", &input));
+        assert!(evaluate("device != mouse", &input));
+    }
+
+    #[test]
+    fn test_idle() {
+        let input = TestInput {
+            idle: Duration::from_secs(45),
+            ..Default::default()
+        };
+        assert!(evaluate("idle > 30s", &input));
+        assert!(evaluate("idle < 1m", &input));
+        assert!(!evaluate("idle >= 45001ms", &input));
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        let expression: Expression = "key:A || mouse:Right && idle<=2".parse().unwrap();
+        assert_eq!(
+            Expression::Or(
+                Box::new(Expression::Key("a".to_string())),
+                Box::new(Expression::And(
+                    Box::new(Expression::Mouse("right".to_string())),
+                    Box::new(Expression::Idle(
+                        Comparison::LessOrEqual,
+                        Duration::from_secs(2)
+                    )),
+                )),
+            ),
+            expression
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err(ExpressionError::UnknownInput("keys:A".to_string())),
+            "keys:A".parse::<Expression>()
+        );
+        assert_eq!(
+            Err(ExpressionError::UnknownDevice("tablet".to_string())),
+            "device == tablet".parse::<Expression>()
+        );
+        assert_eq!(
+            Err(ExpressionError::InvalidDuration("30h".to_string())),
+            "idle > 30h".parse::<Expression>()
+        );
+        assert!(matches!(
+            "key:A &&".parse::<Expression>(),
+            Err(ExpressionError::Unexpected { position: 8, .. })
+        ));
+        assert!(matches!(
+            "(key:A".parse::<Expression>(),
+            Err(ExpressionError::Unexpected { position: 6, .. })
+        ));
+        assert!(matches!(
+            "key:A & key:B".parse::<Expression>(),
+            Err(ExpressionError::Unexpected { position: 6, .. })
+        ));
+    }
+}
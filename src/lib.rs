@@ -1,11 +1,18 @@
-use clap::Parser;
-use log::debug;
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 pub mod args;
 mod avatar;
+mod clock;
+mod expression;
+mod logging;
 mod models;
+mod render;
+mod session;
+mod skin;
+mod stream;
 mod user_input;
 mod view_models;
 
@@ -25,24 +32,34 @@ pub(crate) use self::args::{default_config, default_skin_dir};
 pub(crate) use self::args::{DEFAULT_CONFIG_NAME, DEFAULT_SKIN_DIR_NAME};
 pub use self::errors::PTuberError;
 pub use self::errors::Result as PtuberResult;
-pub(crate) use self::os_ui::{get_window_finder, WindowFinderError, WindowFinderImpl, WindowFinder};
+pub use self::logging::init_logging;
+pub(crate) use self::logging::recent_log_lines;
+pub(crate) use self::os_ui::{get_window_finder, Monitor, WindowFinderError, WindowFinderImpl, WindowFinder};
 
-use self::args::Args;
-use self::avatar::PtuberWindow;
+use self::args::{Args, Command, SkinCommand};
+use self::avatar::{
+    render_session, run_headless, Avatar, ClipRecorder, PtuberWindow, SceneOutput, StreamOutput,
+    TextureStore, VideoOutput,
+};
+use self::render::VideoWriter;
 use self::config::Config;
+use self::session::{Session, SessionEvent, SessionRecorder};
 
 pub const MAX_FRAMERATE: u32 = 60;
 pub const GAMEPAD_POLL_DURATION: Duration = Duration::from_millis(200);
 
-pub struct PTuber<'a> {
+pub struct PTuber {
     config: Config,
-    display: PtuberWindow<'a>,
+    /// `None` with `--no-window`, when `outputs` are all there is
+    display: Option<PtuberWindow>,
+    /// Video, stream and clip outputs for running without a window
+    outputs: Vec<Box<dyn SceneOutput>>,
     user_input_monitor: UserInputMonitor,
+    recorder: Option<Arc<SessionRecorder>>,
 }
 
-impl<'a> PTuber<'a> {
-    pub fn new() -> PtuberResult<Self> {
-        let args = Self::parse_args();
+impl PTuber {
+    pub fn new(args: Args) -> PtuberResult<Self> {
         debug!("Skin path: {:?}", args.skin_dir());
         debug!("Config path: {:?}", args.config_path());
         let mut joystick = None;
@@ -55,11 +72,40 @@ impl<'a> PTuber<'a> {
             mouse_stick = config.gamepad.mouse_move_joystick;
         }
         let user_input_monitor  = UserInputMonitor::new(joystick, mouse_stick);
-        let display = PtuberWindow::new(&args.skin_dir(), config.clone())?;
+        let clips = ClipRecorder::new(&config);
+        let mut outputs: Vec<Box<dyn SceneOutput>> = Vec::new();
+        if let Some(path) = &args.video_out {
+            let writer = VideoWriter::open(path, args.video_format, args.video_fps)?;
+            outputs.push(Box::new(VideoOutput::new(writer)));
+        }
+        if config.stream.enabled {
+            outputs.push(Box::new(StreamOutput::start(&config.stream, clips.control())?));
+        }
+        let display = if args.no_window {
+            // Without a stream to control them, clips can't be asked for
+            if config.stream.enabled {
+                outputs.push(Box::new(clips));
+            }
+            None
+        } else {
+            let mut display = PtuberWindow::new(&args.skin_dir(), config.clone())?;
+            display.set_clip_control(clips.control());
+            for output in outputs.drain(..) {
+                display.add_output(output);
+            }
+            display.add_output(Box::new(clips));
+            Some(display)
+        };
+        let recorder = match &args.record {
+            Some(path) => Some(Arc::new(SessionRecorder::create(Path::new(path))?)),
+            None => None,
+        };
         Ok(Self {
             config,
             display,
+            outputs,
             user_input_monitor,
+            recorder,
         })
     }
     pub fn start_ptuber(&mut self) -> PtuberResult<()> {
@@ -80,16 +126,99 @@ impl<'a> PTuber<'a> {
             .add_keyboard_callback(move |e| keyboard_callback.handle_event(e))
             .expect("adding keyboard callback");
 
-        match self
-            .display
-            .display(&keyboard_viewmodel, &device_viewmodel, &mut self.user_input_monitor)
-        {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err),
+        // Kept alive until the window closes, like the guards above
+        let _record_guards = self.recorder.clone().map(|recorder| {
+            let device_recorder = recorder.clone();
+            let device_guard = self
+                .user_input_monitor
+                .add_device_callback(move |e| device_recorder.record(SessionEvent::from_device(e)))
+                .expect("adding device recorder");
+            let keyboard_guard = self
+                .user_input_monitor
+                .add_keyboard_callback(move |e| recorder.record(SessionEvent::from_keyboard(e)))
+                .expect("adding keyboard recorder");
+            (device_guard, keyboard_guard)
+        });
+
+        if let Some(display) = &mut self.display {
+            return display.display(
+                &keyboard_viewmodel,
+                &device_viewmodel,
+                &mut self.user_input_monitor,
+            );
+        }
+        if self.outputs.is_empty() {
+            warn!("Nothing to do without a window, pass --video-out or enable [stream]");
+            return Ok(());
+        }
+        run_headless(
+            self.config.clone(),
+            std::mem::take(&mut self.outputs),
+            &keyboard_viewmodel,
+            &device_viewmodel,
+            &mut self.user_input_monitor,
+        )
+    }
+}
+
+/// Runs the non-interactive subcommand given in `args`, if any
+pub fn run_command(args: &Args) -> PtuberResult<()> {
+    match &args.command {
+        Some(Command::Skin(SkinCommand::Extract { dir, force })) => {
+            skin::extract_default_skin(&PathBuf::from(dir), *force)?;
+        }
+        Some(Command::Skin(SkinCommand::Calibrate { preview, dry_run })) => {
+            let mut config = Config::load(&args.config_path(), &args.skin_dir())?;
+            skin::calibrate_skin(&mut config, &PathBuf::from(preview), *dry_run)?;
+        }
+        Some(Command::Skin(SkinCommand::Optimize { out })) => {
+            let config = Config::new(&args.config_path(), &args.skin_dir());
+            let report = skin::optimize_skin(&config, &PathBuf::from(out))?;
+            for skipped in &report.skipped {
+                println!("Copied animated {} unchanged", skipped);
+            }
+            println!(
+                "Texture memory: {:.1} KiB -> {:.1} KiB",
+                report.before as f64 / 1024.0,
+                report.after as f64 / 1024.0
+            );
         }
+        Some(Command::Skin(SkinCommand::Memory { dirs })) => {
+            let mut skins: Vec<(PathBuf, PathBuf)> = dirs
+                .iter()
+                .map(|dir| (PathBuf::from(dir), Path::new(dir).join(DEFAULT_CONFIG_NAME)))
+                .collect();
+            if skins.is_empty() {
+                skins.push((args.skin_dir(), args.config_path()));
+            }
+            for (dir, config_path) in skins {
+                print_texture_memory(&dir, &config_path)?;
+            }
+        }
+        Some(Command::Render {
+            input,
+            out,
+            fps,
+            opaque,
+        }) => {
+            let config = Config::new(&args.config_path(), &args.skin_dir());
+            let mut session = Session::load(Path::new(input))?;
+            let frames = render_session(config, &mut session, Path::new(out), *fps, *opaque)?;
+            println!("Wrote {} frames to {}", frames, out);
+        }
+        None => {}
     }
+    Ok(())
+}
 
-    fn parse_args() -> Args {
-        Args::parse()
+fn print_texture_memory(skin_dir: &Path, config_path: &Path) -> PtuberResult<()> {
+    let config = Config::load(config_path, skin_dir)?;
+    let avatar = Avatar::new(TextureStore::software(), skin_dir, config)?;
+    let store = avatar.texture_store();
+    println!("{}:", skin_dir.display());
+    for (source, frames, bytes) in store.memory_report() {
+        println!("  {:>10.1} KiB  {:>4} frame(s)  {}", bytes as f64 / 1024.0, frames, source);
     }
+    println!("  {:>10.1} KiB  total", store.memory_usage() as f64 / 1024.0);
+    Ok(())
 }
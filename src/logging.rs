@@ -0,0 +1,95 @@
+use env_logger::Logger;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Number of log lines kept for the HUD
+const RECENT_LINES: usize = 6;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Logs through env_logger and keeps this crate's latest info and above lines for the HUD
+struct RecordingLogger {
+    inner: Logger,
+}
+
+fn push_line(lines: &mut VecDeque<String>, line: String) {
+    if lines.len() == RECENT_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// Whether `target` is this crate or one of its modules
+fn is_own_target(target: &str) -> bool {
+    let name = env!("CARGO_CRATE_NAME");
+    target
+        .strip_prefix(name)
+        .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+fn is_recorded(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info && is_own_target(metadata.target())
+}
+
+impl Log for RecordingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        is_recorded(metadata) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if is_recorded(record.metadata()) {
+            if let Ok(mut lines) = RECENT.lock() {
+                push_line(&mut lines, format!("{:<5} {}", record.level(), record.args()));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Sets up logging as configured by `RUST_LOG`, also recording recent lines
+pub fn init_logging() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(LevelFilter::Info);
+    if log::set_boxed_logger(Box::new(RecordingLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// The most recent log lines, oldest first
+pub fn recent_log_lines() -> Vec<String> {
+    RECENT
+        .lock()
+        .map(|lines| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_line_keeps_latest() {
+        let mut lines = VecDeque::new();
+        for i in 0..RECENT_LINES + 2 {
+            push_line(&mut lines, i.to_string());
+        }
+        assert_eq!(RECENT_LINES, lines.len());
+        assert_eq!(Some("2"), lines.front().map(String::as_str));
+        assert_eq!(Some((RECENT_LINES + 1).to_string()), lines.back().cloned());
+    }
+
+    #[test]
+    fn test_only_own_lines_are_recorded() {
+        assert!(is_own_target(module_path!()));
+        assert!(is_own_target(env!("CARGO_CRATE_NAME")));
+        assert!(!is_own_target("tiny_http"));
+        assert!(!is_own_target(&format!("{}_extra", env!("CARGO_CRATE_NAME"))));
+    }
+}
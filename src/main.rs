@@ -1,6 +1,8 @@
 use cfg_if::cfg_if;
+use clap::Parser;
 use log::error;
-use ptuber::{PTuber, PtuberResult as Result};
+use ptuber::args::Args;
+use ptuber::{init_logging, run_command, PTuber, PtuberResult as Result};
 #[cfg(windows)]
 use windows::Win32::System::Console::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
 
@@ -22,8 +24,15 @@ fn main() -> Result<()> {
             attach_to_console();
         }
     }
-    env_logger::init();
-    match PTuber::new() {
+    init_logging();
+    let args = Args::parse();
+    if args.command.is_some() {
+        return run_command(&args).map_err(|err| {
+            error!("Got Error (Command): {:?}", err);
+            err
+        });
+    }
+    match PTuber::new(args) {
         Ok(mut ptuber) => {
             ptuber.start_ptuber().map_err(|err| {
                 error!("Got Error (Run): {:?}", err);
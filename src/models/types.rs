@@ -6,7 +6,8 @@ use gilrs::Button;
 use strum_macros::EnumString;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     Mouse,
     GamePad
@@ -45,7 +46,8 @@ impl Default for DeviceType {
     }
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceButton {
     Left,
     Right,
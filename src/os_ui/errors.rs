@@ -10,6 +10,8 @@ cfg_if::cfg_if! {
             Connection(#[from] x11rb::rust_connection::ConnectionError),
             #[error("x11rb Reply error")]
             Error(#[from] x11rb::errors::ReplyError),
+            #[error("x11rb id generation")]
+            Id(#[from] x11rb::errors::ReplyOrIdError),
         }
         pub type WindowFinderError = LinuxFinderError;
     } else if #[cfg(windows)] {
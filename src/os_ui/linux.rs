@@ -1,23 +1,61 @@
 use log::trace;
 use sfml::system::Vector2i;
+use sfml::window::Handle;
 use std::sync::Arc;
+use x11rb::connection::Connection;
 use x11rb::protocol::randr::ConnectionExt as randrConnectionExt;
-use x11rb::protocol::xproto::ConnectionExt;
-use x11rb::protocol::xproto::Window;
+use x11rb::protocol::shape::{ConnectionExt as shapeConnectionExt, SK, SO};
+use x11rb::protocol::xproto::{
+    AtomEnum, ClientMessageEvent, ClipOrdering, ColormapAlloc, ConnectionExt,
+    CreateWindowAux, EventMask, MapState, PropMode, VisualClass, Window, WindowClass,
+};
 use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as wrapperConnectionExt;
 
-use super::{WindowFinder, WindowFinderError};
+use super::{Monitor, WindowFinder, WindowFinderError};
+
+/// `_NET_WM_STATE` client message actions from the EWMH spec
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+/// Marks the request as coming from a normal application
+const SOURCE_APPLICATION: u32 = 1;
+/// `_MOTIF_WM_HINTS` flag saying the decorations field is set
+const MWM_HINTS_DECORATIONS: u32 = 2;
+const ARGB_DEPTH: u8 = 32;
+
+// Atoms the finder uses, interned once when it connects
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_WM_STATE,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_HIDDEN,
+        _MOTIF_WM_HINTS,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct LinuxWindowFinder {
     connection: Arc<RustConnection>,
+    screen_num: usize,
+    atoms: Atoms,
 }
 
 impl LinuxWindowFinder {
     pub fn new() -> Result<Self, WindowFinderError> {
-        let (connection, _screen_num) = x11rb::connect(None)?;
+        let (connection, screen_num) = x11rb::connect(None)?;
+        let atoms = Atoms::new(&connection)?.reply()?;
         let connection = Arc::new(connection);
-        Ok(Self { connection })
+        Ok(Self {
+            connection,
+            screen_num,
+            atoms,
+        })
+    }
+
+    fn root(&self) -> Window {
+        self.connection.setup().roots[self.screen_num].root
     }
 
     fn get_focused_window(&self) -> Result<Window, WindowFinderError> {
@@ -45,4 +83,157 @@ impl WindowFinder for LinuxWindowFinder {
         let screen = screen_info.modes[0];
         Ok(Vector2i::new(screen.width.into(), screen.height.into()))
     }
+
+    fn monitors(&self) -> Result<Vec<Monitor>, WindowFinderError> {
+        let reply = self.connection.randr_get_monitors(self.root(), true)?.reply()?;
+        let mut monitors = Vec::with_capacity(reply.monitors.len());
+        for info in reply.monitors {
+            let name = self.connection.get_atom_name(info.name)?.reply()?.name;
+            monitors.push(Monitor {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                position: Vector2i::new(info.x.into(), info.y.into()),
+                size: Vector2i::new(info.width.into(), info.height.into()),
+            });
+        }
+        Ok(monitors)
+    }
+
+    fn set_always_on_top(&self, window: Handle, enabled: bool) -> Result<(), WindowFinderError> {
+        let window = window as Window;
+        let net_wm_state = self.atoms._NET_WM_STATE;
+        let above = self.atoms._NET_WM_STATE_ABOVE;
+        let action = if enabled {
+            NET_WM_STATE_ADD
+        } else {
+            NET_WM_STATE_REMOVE
+        };
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            net_wm_state,
+            [action, above, 0, SOURCE_APPLICATION, 0],
+        );
+        // The window manager owns the state, so ask it through the root window
+        self.connection.send_event(
+            false,
+            self.root(),
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    fn create_transparent_window(
+        &self,
+        width: u32,
+        height: u32,
+        title: &str,
+        borderless: bool,
+    ) -> Result<Option<Handle>, WindowFinderError> {
+        let screen = &self.connection.setup().roots[self.screen_num];
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == ARGB_DEPTH)
+            .flat_map(|depth| depth.visuals.iter())
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR);
+        let visual = match visual {
+            Some(visual) => visual.visual_id,
+            None => return Ok(None),
+        };
+        trace!("ARGB visual: {}", visual);
+
+        let colormap = self.connection.generate_id()?;
+        self.connection
+            .create_colormap(ColormapAlloc::NONE, colormap, screen.root, visual)?;
+        let window = self.connection.generate_id()?;
+        // A border pixel and colormap are required when the visual differs from the parent's
+        let aux = CreateWindowAux::new()
+            .background_pixel(0)
+            .border_pixel(0)
+            .colormap(colormap);
+        self.connection.create_window(
+            ARGB_DEPTH,
+            window,
+            screen.root,
+            0,
+            0,
+            width as u16,
+            height as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &aux,
+        )?;
+        self.connection.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            title.as_bytes(),
+        )?;
+        // Lets the close button send WM_DELETE_WINDOW, which SFML turns into `Event::Closed`,
+        // instead of the window manager killing the connection
+        self.connection.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms.WM_PROTOCOLS,
+            AtomEnum::ATOM,
+            &[self.atoms.WM_DELETE_WINDOW],
+        )?;
+        if borderless {
+            let motif_hints = self.atoms._MOTIF_WM_HINTS;
+            self.connection.change_property32(
+                PropMode::REPLACE,
+                window,
+                motif_hints,
+                motif_hints,
+                &[MWM_HINTS_DECORATIONS, 0, 0, 0, 0],
+            )?;
+        }
+        self.connection.map_window(window)?;
+        self.connection.flush()?;
+        Ok(Some(window as Handle))
+    }
+
+    fn is_window_visible(&self, window: Handle) -> Result<bool, WindowFinderError> {
+        let window = window as Window;
+        // Minimized windows are unmapped by most window managers, the rest mark them hidden
+        let attributes = self.connection.get_window_attributes(window)?.reply()?;
+        if attributes.map_state != MapState::VIEWABLE {
+            return Ok(false);
+        }
+        let net_wm_state = self.atoms._NET_WM_STATE;
+        let hidden = self.atoms._NET_WM_STATE_HIDDEN;
+        let state = self
+            .connection
+            .get_property(false, window, net_wm_state, AtomEnum::ATOM, 0, u32::MAX)?
+            .reply()?;
+        let is_hidden = state
+            .value32()
+            .map_or(false, |mut atoms| atoms.any(|atom| atom == hidden));
+        Ok(!is_hidden)
+    }
+
+    fn set_click_through(&self, window: Handle, enabled: bool) -> Result<(), WindowFinderError> {
+        let window = window as Window;
+        if enabled {
+            // An empty input region lets every click fall through to the window below
+            self.connection.shape_rectangles(
+                SO::SET,
+                SK::INPUT,
+                ClipOrdering::UNSORTED,
+                window,
+                0,
+                0,
+                &[],
+            )?;
+        } else {
+            self.connection
+                .shape_mask(SO::SET, SK::INPUT, window, 0, 0, x11rb::NONE)?;
+        }
+        self.connection.flush()?;
+        Ok(())
+    }
 }
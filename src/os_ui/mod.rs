@@ -13,8 +13,26 @@ pub(crate) use self::windows::WindowsWindowFinder as WindowFinderImpl;
 
 use dyn_clone::{clone_trait_object, DynClone};
 use sfml::system::{Vector2f, Vector2i};
+use sfml::window::Handle;
 use std::fmt::Debug;
 
+/// A monitor's name and its area on the desktop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    pub name: String,
+    pub position: Vector2i,
+    pub size: Vector2i,
+}
+
+impl Monitor {
+    pub fn contains(&self, point: Vector2i) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < self.position.x + self.size.x
+            && point.y < self.position.y + self.size.y
+    }
+}
+
 pub trait WindowFinder: Debug + DynClone {
     fn get_focused_window_size(&self) -> Result<Vector2i, WindowFinderError>;
     fn get_cursor_position(&self) -> Result<Vector2f, WindowFinderError> {
@@ -25,6 +43,34 @@ pub trait WindowFinder: Debug + DynClone {
         Ok(Vector2f::new(x, y))
     }
     fn get_focused_screen_size(&self) -> Result<Vector2i, WindowFinderError>;
+    /// Every connected monitor, empty where the platform can't tell them apart
+    fn monitors(&self) -> Result<Vec<Monitor>, WindowFinderError> {
+        Ok(Vec::new())
+    }
+    /// Keeps `window` above all other windows
+    fn set_always_on_top(&self, _window: Handle, _enabled: bool) -> Result<(), WindowFinderError> {
+        Ok(())
+    }
+    /// Creates a window whose pixels carry alpha, for compositing window managers.
+    ///
+    /// Returns `None` where that isn't supported, so a normal window can be used instead.
+    fn create_transparent_window(
+        &self,
+        _width: u32,
+        _height: u32,
+        _title: &str,
+        _borderless: bool,
+    ) -> Result<Option<Handle>, WindowFinderError> {
+        Ok(None)
+    }
+    /// Whether `window` is shown at all, false while it is minimized or hidden
+    fn is_window_visible(&self, _window: Handle) -> Result<bool, WindowFinderError> {
+        Ok(true)
+    }
+    /// Lets mouse clicks go through `window` to whatever is below it
+    fn set_click_through(&self, _window: Handle, _enabled: bool) -> Result<(), WindowFinderError> {
+        Ok(())
+    }
 }
 
 clone_trait_object!(WindowFinder);
@@ -1,9 +1,12 @@
 use sfml::system::Vector2i;
+use sfml::window::Handle;
 use windows::Win32::Foundation::HWND;
 use windows::Win32::Graphics::Gdi::{
     GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST,
 };
-use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindowRect, IsIconic, IsWindowVisible,
+};
 
 use super::{WindowFinder, WindowFinderError};
 
@@ -65,4 +68,13 @@ impl WindowFinder for WindowsWindowFinder {
             info.rcMonitor.bottom - info.rcMonitor.top,
         ))
     }
+
+    fn is_window_visible(&self, window: Handle) -> Result<bool, WindowFinderError> {
+        let window = HWND(window as isize);
+        let visible;
+        unsafe {
+            visible = IsWindowVisible(window).as_bool() && !IsIconic(window).as_bool();
+        }
+        Ok(visible)
+    }
 }
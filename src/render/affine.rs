@@ -0,0 +1,94 @@
+/// 2D affine transform laid out like SFML's: `[a, b, tx, c, d, ty]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub matrix: [f32; 6],
+}
+
+impl Affine {
+    pub fn identity() -> Self {
+        Self {
+            matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        }
+    }
+
+    /// Same transform an SFML sprite with the given properties draws with
+    pub fn sprite(position: (f32, f32), origin: (f32, f32), scale: (f32, f32), degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let a = cos * scale.0;
+        let b = -sin * scale.1;
+        let c = sin * scale.0;
+        let d = cos * scale.1;
+        let tx = position.0 - origin.0 * a - origin.1 * b;
+        let ty = position.1 - origin.0 * c - origin.1 * d;
+        Self {
+            matrix: [a, b, tx, c, d, ty],
+        }
+    }
+
+    /// Transform applying `other` first and then `self`, like SFML's `Transform::combine`
+    pub fn combine(&self, other: &Affine) -> Self {
+        let [a, b, tx, c, d, ty] = self.matrix;
+        let [oa, ob, otx, oc, od, oty] = other.matrix;
+        Self {
+            matrix: [
+                a * oa + b * oc,
+                a * ob + b * od,
+                a * otx + b * oty + tx,
+                c * oa + d * oc,
+                c * ob + d * od,
+                c * otx + d * oty + ty,
+            ],
+        }
+    }
+
+    pub fn apply(&self, point: (f32, f32)) -> (f32, f32) {
+        let [a, b, tx, c, d, ty] = self.matrix;
+        (a * point.0 + b * point.1 + tx, c * point.0 + d * point.1 + ty)
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let [a, b, tx, c, d, ty] = self.matrix;
+        let det = a * d - b * c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let ia = d / det;
+        let ib = -b / det;
+        let ic = -c / det;
+        let id = a / det;
+        Some(Self {
+            matrix: [ia, ib, -(ia * tx + ib * ty), ic, id, -(ic * tx + id * ty)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: (f32, f32), actual: (f32, f32)) {
+        assert!((expected.0 - actual.0).abs() < 1e-3, "{:?} != {:?}", expected, actual);
+        assert!((expected.1 - actual.1).abs() < 1e-3, "{:?} != {:?}", expected, actual);
+    }
+
+    #[test]
+    fn test_sprite_transform_maps_origin_to_position() {
+        let transform = Affine::sprite((100.0, 50.0), (10.0, 0.0), (1.0, 2.0), 30.0);
+        assert_close((100.0, 50.0), transform.apply((10.0, 0.0)));
+    }
+
+    #[test]
+    fn test_inverse_round_trip() {
+        let transform = Affine::sprite((12.0, -4.0), (3.0, 7.0), (2.0, 0.5), 75.0);
+        let inverse = transform.inverse().unwrap();
+        assert_close((5.0, 9.0), inverse.apply(transform.apply((5.0, 9.0))));
+    }
+
+    #[test]
+    fn test_combine_applies_right_side_first() {
+        let scale = Affine::sprite((0.0, 0.0), (0.0, 0.0), (2.0, 2.0), 0.0);
+        let shift = Affine::sprite((10.0, 0.0), (0.0, 0.0), (1.0, 1.0), 0.0);
+        assert_close((12.0, 2.0), shift.combine(&scale).apply((1.0, 1.0)));
+        assert_close((22.0, 2.0), scale.combine(&shift).apply((1.0, 1.0)));
+    }
+}
@@ -0,0 +1,196 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{straight_alpha, RenderError, RenderResult};
+use crate::config::ClipFormat;
+
+/// Shortest delay a clip frame gets, browsers slow down anything faster
+const MIN_DELAY: Duration = Duration::from_millis(20);
+
+/// The last few seconds of frames, with runs of identical frames kept once
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayBuffer {
+    length: Duration,
+    /// Frames and when they were first shown, oldest first
+    frames: VecDeque<(Instant, Arc<RgbaImage>)>,
+}
+
+impl ReplayBuffer {
+    pub fn new(length: Duration) -> Self {
+        Self {
+            length,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Adds the frame shown from `now` on, starting over when the scene changed size
+    pub fn push(&mut self, now: Instant, image: Arc<RgbaImage>) {
+        // A clip's frames all have to be the same size
+        let resized = self
+            .frames
+            .back()
+            .is_some_and(|(_, last)| last.dimensions() != image.dimensions());
+        if resized {
+            self.frames.clear();
+        }
+        let unchanged = self.frames.back().is_some_and(|(_, last)| **last == *image);
+        if !unchanged {
+            self.frames.push_back((now, image));
+        }
+        // Keep the frame that was showing when the replay starts
+        let start = now.checked_sub(self.length).unwrap_or(now);
+        while self.frames.len() > 1 && self.frames[1].0 <= start {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The replay up to `now`, each frame with how long it shows
+    pub fn clip(&self, now: Instant) -> Vec<(Arc<RgbaImage>, Duration)> {
+        let start = now.checked_sub(self.length).unwrap_or(now);
+        let ends = self.frames.iter().skip(1).map(|(time, _)| *time).chain([now]);
+        self.frames
+            .iter()
+            .zip(ends)
+            .map(|((time, image), end)| {
+                let shown = end.saturating_duration_since((*time).max(start));
+                (image.clone(), shown.max(MIN_DELAY))
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn save_png(image: &RgbaImage, path: &Path) -> RenderResult<()> {
+    let mut image = image.clone();
+    straight_alpha(&mut image);
+    image.save(path)?;
+    Ok(())
+}
+
+fn save_gif(frames: &[(Arc<RgbaImage>, Duration)], path: &Path) -> RenderResult<()> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+    for (image, shown) in frames {
+        let mut image = (**image).clone();
+        straight_alpha(&mut image);
+        let delay = Delay::from_saturating_duration(*shown);
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+fn save_apng(frames: &[(Arc<RgbaImage>, Duration)], path: &Path) -> RenderResult<()> {
+    let (width, height) = match frames.first() {
+        Some((first, _)) => first.dimensions(),
+        None => return Err(RenderError::EmptyClip),
+    };
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut writer = encoder.write_header()?;
+    for (image, shown) in frames {
+        let mut image = (**image).clone();
+        straight_alpha(&mut image);
+        let millis = shown.as_millis().min(u16::MAX as u128) as u16;
+        writer.set_frame_delay(millis, 1000)?;
+        writer.write_image_data(image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Saves `frames` as an animation looping forever, there has to be at least one
+pub(crate) fn save_clip(
+    frames: &[(Arc<RgbaImage>, Duration)],
+    format: ClipFormat,
+    path: &Path,
+) -> RenderResult<()> {
+    if frames.is_empty() {
+        return Err(RenderError::EmptyClip);
+    }
+    match format {
+        ClipFormat::Gif => save_gif(frames, path),
+        ClipFormat::Apng => save_apng(frames, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{AnimationDecoder, Rgba};
+
+    fn frame(value: u8) -> Arc<RgbaImage> {
+        Arc::new(RgbaImage::from_pixel(2, 2, Rgba([value, value, value, 255])))
+    }
+
+    #[test]
+    fn test_replay_keeps_the_last_seconds() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut buffer = ReplayBuffer::new(Duration::from_millis(250));
+        buffer.push(at(0), frame(1));
+        buffer.push(at(100), frame(1));
+        buffer.push(at(200), frame(2));
+        buffer.push(at(300), frame(3));
+        let clip = buffer.clip(at(400));
+        let shown: Vec<_> = clip.iter().map(|(_, shown)| shown.as_millis()).collect();
+        assert_eq!(vec![50, 100, 100], shown);
+        assert_eq!(frame(1), clip[0].0);
+
+        buffer.push(at(500), frame(3));
+        assert_eq!(2, buffer.clip(at(500)).len());
+    }
+
+    #[test]
+    fn test_replay_starts_over_when_resized() {
+        let start = Instant::now();
+        let mut buffer = ReplayBuffer::new(Duration::from_secs(10));
+        buffer.push(start, frame(1));
+        buffer.push(start + Duration::from_millis(100), frame(2));
+        let larger = Arc::new(RgbaImage::new(3, 2));
+        buffer.push(start + Duration::from_millis(200), larger.clone());
+        let clip = buffer.clip(start + Duration::from_millis(300));
+        assert_eq!(1, clip.len());
+        assert_eq!(larger, clip[0].0);
+    }
+
+    #[test]
+    fn test_saved_clips_decode() {
+        let frames = vec![
+            (frame(10), Duration::from_millis(100)),
+            (frame(200), Duration::from_millis(50)),
+        ];
+        // The process id keeps test runs going at the same time apart
+        let dir = std::env::temp_dir().join(format!("ptuber_clip_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gif = dir.join("clip.gif");
+        save_clip(&frames, ClipFormat::Gif, &gif).unwrap();
+        let decoder = image::codecs::gif::GifDecoder::new(File::open(&gif).unwrap()).unwrap();
+        assert_eq!(2, decoder.into_frames().count());
+
+        let apng = dir.join("clip.png");
+        save_clip(&frames, ClipFormat::Apng, &apng).unwrap();
+        let decoder = image::codecs::png::PngDecoder::new(File::open(&apng).unwrap()).unwrap();
+        assert!(decoder.is_apng());
+        let decoded: Vec<_> = decoder.apng().into_frames().collect();
+        assert_eq!(2, decoded.len());
+        assert_eq!(200, decoded[1].as_ref().unwrap().buffer().get_pixel(0, 0)[0]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_empty_clip_is_an_error() {
+        let path = Path::new("never_written.png");
+        for format in [ClipFormat::Gif, ClipFormat::Apng] {
+            assert!(matches!(save_clip(&[], format, path), Err(RenderError::EmptyClip)));
+        }
+        assert!(matches!(save_apng(&[], path), Err(RenderError::EmptyClip)));
+        assert!(!path.exists());
+    }
+}
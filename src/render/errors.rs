@@ -0,0 +1,26 @@
+use core::result;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    #[error("writing frames")]
+    File(#[from] io::Error),
+    #[error("encoding frame")]
+    Encode(#[from] image::ImageError),
+    #[error("encoding APNG")]
+    Apng(#[from] png::EncodingError),
+    #[error("clip has no frames")]
+    EmptyClip,
+    #[error("shaders are configured but this system can't run them")]
+    NoShaders,
+    #[error("video reader closed the stream")]
+    Closed,
+    #[error("frame is {size:?} but the video is {expected:?}")]
+    FrameSize {
+        expected: (u32, u32),
+        size: (u32, u32),
+    },
+}
+
+pub type RenderResult<T> = result::Result<T, RenderError>;
@@ -0,0 +1,19 @@
+mod affine;
+mod clip;
+mod errors;
+mod renderer;
+mod shaders;
+mod sfml_renderer;
+mod software;
+mod video;
+
+pub(crate) use self::affine::Affine;
+pub(crate) use self::clip::{save_clip, save_png, ReplayBuffer};
+pub(crate) use self::errors::{RenderError, RenderResult};
+pub(crate) use self::renderer::{
+    upload_image, Backend, DrawStates, Frame, Renderer, Shape, ShapeKind, SpriteDraw,
+};
+pub(crate) use self::sfml_renderer::{Presentable, SfmlRenderer};
+pub(crate) use self::shaders::{ShaderChain, ShaderInputs};
+pub(crate) use self::software::{texture_pixels, SoftwareRenderer};
+pub(crate) use self::video::{straight_alpha, VideoWriter};
@@ -0,0 +1,167 @@
+use image::RgbaImage;
+use sfml::graphics::{Color, FloatRect, Image, IntRect, Texture};
+use sfml::system::{Vector2f, Vector2u};
+use sfml::SfBox;
+
+use super::Affine;
+use crate::config::BlendMode;
+
+/// Where skin images are kept and drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Backend {
+    /// Uploaded to the GPU and drawn by SFML
+    #[default]
+    Sfml,
+    /// Kept in memory and drawn by `SoftwareRenderer`, no display or GL needed
+    Software,
+}
+
+/// One frame of a skin image as the backend holds it
+#[derive(Debug)]
+pub(crate) enum Frame {
+    Texture(SfBox<Texture>),
+    Image { image: RgbaImage, smooth: bool },
+}
+
+impl Frame {
+    pub fn size(&self) -> Vector2u {
+        match self {
+            Self::Texture(texture) => texture.size(),
+            Self::Image { image, .. } => Vector2u::new(image.width(), image.height()),
+        }
+    }
+}
+
+/// Copies `image` into a new texture
+pub(crate) fn upload_image(image: &RgbaImage, smooth: bool) -> Option<SfBox<Texture>> {
+    let (width, height) = image.dimensions();
+    // SAFETY: an RgbaImage buffer always holds exactly width * height RGBA pixels
+    let image = unsafe { Image::create_from_pixels(width, height, image.as_raw()) }?;
+    let mut texture = Texture::from_image(&image, IntRect::default()).ok()?;
+    texture.set_smooth(smooth);
+    Some(texture)
+}
+
+/// A frame placed on the canvas, transformed like an SFML sprite
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpriteDraw<'a> {
+    pub frame: &'a Frame,
+    pub position: Vector2f,
+    pub origin: Vector2f,
+    pub scale: Vector2f,
+    /// Clockwise, in degrees
+    pub rotation: f32,
+    /// Multiplied with every pixel
+    pub color: Color,
+}
+
+impl<'a> SpriteDraw<'a> {
+    pub fn new(frame: &'a Frame) -> Self {
+        Self {
+            frame,
+            position: Vector2f::new(0.0, 0.0),
+            origin: Vector2f::new(0.0, 0.0),
+            scale: Vector2f::new(1.0, 1.0),
+            rotation: 0.0,
+            color: Color::WHITE,
+        }
+    }
+
+    pub fn transform(&self) -> Affine {
+        Affine::sprite(
+            (self.position.x, self.position.y),
+            (self.origin.x, self.origin.y),
+            (self.scale.x, self.scale.y),
+            self.rotation,
+        )
+    }
+}
+
+/// How a sprite is combined with what is already drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DrawStates {
+    pub transform: Affine,
+    pub blend: BlendMode,
+}
+
+impl Default for DrawStates {
+    fn default() -> Self {
+        Self {
+            transform: Affine::identity(),
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShapeKind {
+    Rectangle,
+    /// A circle as wide as the shape
+    Circle,
+}
+
+/// A filled and outlined shape, the outline is drawn outside of `size` like SFML does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Shape {
+    pub kind: ShapeKind,
+    pub size: Vector2f,
+    pub position: Vector2f,
+    pub origin: Vector2f,
+    /// Clockwise, in degrees
+    pub rotation: f32,
+    pub fill: Color,
+    pub outline: Color,
+    pub outline_thickness: f32,
+}
+
+impl Shape {
+    pub fn rectangle(size: Vector2f) -> Self {
+        Self {
+            kind: ShapeKind::Rectangle,
+            size,
+            position: Vector2f::new(0.0, 0.0),
+            origin: Vector2f::new(0.0, 0.0),
+            rotation: 0.0,
+            fill: Color::WHITE,
+            outline: Color::TRANSPARENT,
+            outline_thickness: 0.0,
+        }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self {
+            kind: ShapeKind::Circle,
+            ..Self::rectangle(Vector2f::new(radius * 2.0, radius * 2.0))
+        }
+    }
+
+    pub fn transform(&self) -> Affine {
+        Affine::sprite(
+            (self.position.x, self.position.y),
+            (self.origin.x, self.origin.y),
+            (1.0, 1.0),
+            self.rotation,
+        )
+    }
+}
+
+/// Something the scene can be drawn onto
+pub(crate) trait Renderer {
+    /// Shows the `scene` sized canvas in `viewport`, given as fractions of the target's size.
+    ///
+    /// Nothing is drawn outside the viewport.
+    fn set_view(&mut self, scene: Vector2f, viewport: FloatRect);
+
+    /// Fills the whole target, outside the viewport too
+    fn clear(&mut self, color: Color);
+
+    /// Draws a frame held by either backend, but one held by the other backend is converted
+    /// on every draw: `SfmlRenderer` uploads the image, `SoftwareRenderer` reads the texture
+    /// back from the GPU. Draw with the renderer matching the `TextureStore`'s backend.
+    fn draw_sprite(&mut self, sprite: &SpriteDraw, states: &DrawStates);
+
+    fn draw_shape(&mut self, shape: &Shape);
+
+    /// Finishes the frame
+    fn present(&mut self);
+}
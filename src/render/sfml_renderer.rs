@@ -0,0 +1,127 @@
+use sfml::graphics::{
+    BlendMode as SfmlBlendMode, CircleShape, Color, FloatRect, RectangleShape, RenderStates,
+    RenderTarget, RenderTexture, RenderWindow, Shape as SfmlShape, Sprite, Transform,
+    Transformable, View,
+};
+use sfml::system::Vector2f;
+
+use super::{upload_image, Affine, DrawStates, Frame, Renderer, Shape, ShapeKind, SpriteDraw};
+use crate::config::BlendMode;
+
+/// Points used for circles, SFML's default
+const CIRCLE_POINTS: usize = 30;
+
+/// A render target that shows what was drawn once the frame is done
+pub(crate) trait Presentable: RenderTarget {
+    fn present(&mut self);
+}
+
+impl Presentable for RenderWindow {
+    fn present(&mut self) {
+        self.display();
+    }
+}
+
+impl Presentable for RenderTexture {
+    fn present(&mut self) {
+        self.display();
+    }
+}
+
+fn sfml_transform(affine: &Affine) -> Transform {
+    let [a, b, tx, c, d, ty] = affine.matrix;
+    Transform::new(a, b, tx, c, d, ty, 0.0, 0.0, 1.0)
+}
+
+fn sfml_blend(blend: BlendMode) -> SfmlBlendMode {
+    match blend {
+        BlendMode::Alpha => SfmlBlendMode::ALPHA,
+        BlendMode::Add => SfmlBlendMode::ADD,
+        BlendMode::Multiply => SfmlBlendMode::MULTIPLY,
+    }
+}
+
+fn style_shape<'s>(shape: &mut impl SfmlShape<'s>, from: &Shape) {
+    shape.set_position(from.position);
+    shape.set_origin(from.origin);
+    shape.set_rotation(from.rotation);
+    shape.set_fill_color(from.fill);
+    shape.set_outline_color(from.outline);
+    shape.set_outline_thickness(from.outline_thickness);
+}
+
+/// Draws through SFML onto a window or texture
+pub(crate) struct SfmlRenderer<'t, T: Presentable> {
+    target: &'t mut T,
+}
+
+impl<'t, T: Presentable> SfmlRenderer<'t, T> {
+    pub fn new(target: &'t mut T) -> Self {
+        Self { target }
+    }
+
+    /// The underlying target, for drawing what the renderer doesn't cover such as text
+    pub fn target(&mut self) -> &mut T {
+        self.target
+    }
+}
+
+impl<'t, T: Presentable> Renderer for SfmlRenderer<'t, T> {
+    fn set_view(&mut self, scene: Vector2f, viewport: FloatRect) {
+        let mut view = View::new(Vector2f::new(scene.x / 2.0, scene.y / 2.0), scene);
+        view.set_viewport(viewport);
+        self.target.set_view(&view);
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.target.clear(color);
+    }
+
+    fn draw_sprite(&mut self, sprite: &SpriteDraw, states: &DrawStates) {
+        // Images kept for the software renderer are uploaded just for this draw, see
+        // `Renderer::draw_sprite`
+        let uploaded;
+        let texture = match sprite.frame {
+            Frame::Texture(texture) => texture,
+            Frame::Image { image, smooth } => match upload_image(image, *smooth) {
+                Some(texture) => {
+                    uploaded = texture;
+                    &uploaded
+                }
+                None => return,
+            },
+        };
+        let mut drawn = Sprite::with_texture(texture);
+        drawn.set_position(sprite.position);
+        drawn.set_origin(sprite.origin);
+        drawn.set_scale(sprite.scale);
+        drawn.set_rotation(sprite.rotation);
+        drawn.set_color(sprite.color);
+        let states = RenderStates::new(
+            sfml_blend(states.blend),
+            sfml_transform(&states.transform),
+            None,
+            None,
+        );
+        self.target.draw_with_renderstates(&drawn, &states);
+    }
+
+    fn draw_shape(&mut self, shape: &Shape) {
+        match shape.kind {
+            ShapeKind::Rectangle => {
+                let mut drawn = RectangleShape::with_size(shape.size);
+                style_shape(&mut drawn, shape);
+                self.target.draw(&drawn);
+            }
+            ShapeKind::Circle => {
+                let mut drawn = CircleShape::new(shape.size.x / 2.0, CIRCLE_POINTS);
+                style_shape(&mut drawn, shape);
+                self.target.draw(&drawn);
+            }
+        }
+    }
+
+    fn present(&mut self) {
+        self.target.present();
+    }
+}
@@ -0,0 +1,313 @@
+use log::warn;
+use sfml::graphics::{
+    glsl, BlendMode, RenderStates, RenderTarget, RenderTexture, Shader, Sprite, Transform,
+    Transformable,
+};
+use sfml::system::{Vector2f, Vector2u, Vector3f};
+use std::fs;
+use std::time::Instant;
+
+use crate::clock;
+use crate::config::{BuiltinShader, ShaderSource, UniformValue};
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+use crate::Config;
+
+/// Input state handed to every pass, for shaders that react to the player
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ShaderInputs {
+    /// Cursor position, 0 to 1 across the screen
+    pub cursor: Vector2f,
+    /// Keys and buttons held down
+    pub keys_down: f32,
+    /// Seconds since the last input
+    pub idle: f32,
+}
+
+impl ShaderInputs {
+    pub fn capture(keyboard: &KeyboardViewModelImpl, mouse: &DeviceViewModelImpl) -> Self {
+        let keys_down = keyboard.keys_pressed().len() + mouse.buttons_pressed().len();
+        let last_input = keyboard.last_input().max(mouse.last_input());
+        Self {
+            cursor: mouse.position(),
+            keys_down: keys_down as f32,
+            idle: clock::now().saturating_duration_since(last_input).as_secs_f32(),
+        }
+    }
+}
+
+fn builtin_source(builtin: BuiltinShader) -> &'static str {
+    match builtin {
+        BuiltinShader::ChromaKey => include_str!("../../assets/shaders/chroma_key.frag"),
+        BuiltinShader::Crt => include_str!("../../assets/shaders/crt.frag"),
+        BuiltinShader::Glow => include_str!("../../assets/shaders/glow.frag"),
+        BuiltinShader::ColorGrade => include_str!("../../assets/shaders/color_grade.frag"),
+    }
+}
+
+/// Values the built-ins start with, the config overrides them one by one
+fn builtin_defaults(builtin: BuiltinShader) -> Vec<(&'static str, UniformValue)> {
+    use UniformValue::{Float, Vector};
+    match builtin {
+        BuiltinShader::ChromaKey => vec![
+            ("key_color", Vector(vec![0.0, 1.0, 0.0])),
+            ("similarity", Float(0.4)),
+            ("smoothness", Float(0.08)),
+            ("spill", Float(0.1)),
+        ],
+        BuiltinShader::Crt => vec![
+            ("scanlines", Float(0.25)),
+            ("curvature", Float(0.1)),
+            ("vignette", Float(0.2)),
+        ],
+        BuiltinShader::Glow => vec![
+            ("glow_color", Vector(vec![1.0, 1.0, 1.0, 1.0])),
+            ("radius", Float(4.0)),
+            ("strength", Float(1.5)),
+        ],
+        BuiltinShader::ColorGrade => vec![
+            ("brightness", Float(0.0)),
+            ("contrast", Float(1.0)),
+            ("saturation", Float(1.0)),
+            ("gamma", Float(1.0)),
+            ("tint", Vector(vec![1.0, 1.0, 1.0])),
+        ],
+    }
+}
+
+/// Whether `source` has a `uniform` named `name`.
+///
+/// SFML complains on every frame about uniforms a shader doesn't have, so only the declared
+/// ones are set.
+fn declares_uniform(source: &str, name: &str) -> bool {
+    source.split(';').any(|statement| {
+        let code: Vec<&str> = statement
+            .lines()
+            .map(|line| line.split("//").next().unwrap_or_default())
+            .collect();
+        let code = code.join(" ");
+        let mut words = code
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty());
+        if words.next() != Some("uniform") {
+            return false;
+        }
+        words
+            .skip(1)
+            .any(|word| word.split('[').next() == Some(name))
+    })
+}
+
+fn set_uniform(shader: &mut Shader<'static>, name: &str, value: &UniformValue) {
+    match value {
+        UniformValue::Float(value) => shader.set_uniform_float(name, *value),
+        UniformValue::Vector(values) => match values[..] {
+            [x, y] => shader.set_uniform_vec2(name, Vector2f::new(x, y)),
+            [x, y, z] => shader.set_uniform_vec3(name, Vector3f::new(x, y, z)),
+            [x, y, z, w] => shader.set_uniform_vec4(name, glsl::Vec4 { x, y, z, w }),
+            _ => warn!("Shader uniform {} needs 2 to 4 values", name),
+        },
+    }
+}
+
+struct ShaderPassState {
+    shader: Shader<'static>,
+    source: String,
+}
+
+impl ShaderPassState {
+    fn load(config: &Config, source: &ShaderSource) -> Option<Self> {
+        let (code, name) = match source {
+            ShaderSource::Builtin { builtin } => {
+                (builtin_source(*builtin).to_string(), format!("{:?}", builtin))
+            }
+            ShaderSource::File { file } => {
+                match fs::read_to_string(config.images_path.join(file)) {
+                    Ok(code) => (code, file.clone()),
+                    Err(err) => {
+                        warn!("Could not read shader {}: {}", file, err);
+                        return None;
+                    }
+                }
+            }
+        };
+        match Shader::from_memory(None, None, Some(&code)) {
+            Some(shader) => Some(Self { shader, source: code }),
+            None => {
+                warn!("Could not compile shader {}, it is left out", name);
+                None
+            }
+        }
+    }
+
+    fn set_declared(&mut self, name: &str, value: &UniformValue) {
+        if declares_uniform(&self.source, name) {
+            set_uniform(&mut self.shader, name, value);
+        }
+    }
+}
+
+/// Post-processing passes run over the composed scene before it reaches the window or an output
+pub(crate) struct ShaderChain {
+    passes: Vec<ShaderPassState>,
+    /// The scene and a texture to ping-pong with, sized to the scene in window pixels
+    targets: Vec<RenderTexture>,
+    started: Instant,
+}
+
+impl std::fmt::Debug for ShaderChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShaderChain")
+            .field("passes", &self.passes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShaderChain {
+    /// Compiles the enabled passes in `config.shaders`, leaving out the ones that fail
+    pub fn load(config: &Config) -> Self {
+        let mut passes = Vec::new();
+        let enabled: Vec<_> = config.shaders.iter().filter(|pass| pass.enabled).collect();
+        if !enabled.is_empty() && !Shader::is_available() {
+            warn!("Shaders are not available on this system, drawing without them");
+            return Self::default();
+        }
+        for pass in enabled {
+            let mut state = match ShaderPassState::load(config, &pass.source) {
+                Some(state) => state,
+                None => continue,
+            };
+            if let ShaderSource::Builtin { builtin } = pass.source {
+                for (name, value) in builtin_defaults(builtin) {
+                    state.set_declared(name, &value);
+                }
+            }
+            for (name, value) in &pass.uniforms {
+                if declares_uniform(&state.source, name) {
+                    set_uniform(&mut state.shader, name, value);
+                } else {
+                    warn!("Shader has no uniform {}, ignoring it", name);
+                }
+            }
+            passes.push(state);
+        }
+        Self {
+            passes,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// The texture to draw the scene into, `size` pixels large
+    pub fn scene_target(&mut self, size: Vector2u) -> Option<&mut RenderTexture> {
+        let size = Vector2u::new(size.x.max(1), size.y.max(1));
+        if self.targets.first().map(|target| target.size()) != Some(size) {
+            self.targets = (0..2)
+                .map(|_| RenderTexture::new(size.x, size.y))
+                .collect::<Option<_>>()
+                .unwrap_or_default();
+        }
+        self.targets.first_mut()
+    }
+
+    /// Runs the passes over the scene and draws the result at `position`, in pixels of `target`.
+    ///
+    /// The target's view is reset to its default one.
+    pub fn apply<T: RenderTarget>(
+        &mut self,
+        target: &mut T,
+        position: Vector2f,
+        inputs: ShaderInputs,
+    ) {
+        if self.targets.len() < 2 {
+            return;
+        }
+        let size = self.targets[0].size();
+        let resolution = Vector2f::new(size.x as f32, size.y as f32);
+        let time = clock::now().saturating_duration_since(self.started).as_secs_f32();
+        let automatic = [
+            ("resolution", UniformValue::Vector(vec![resolution.x, resolution.y])),
+            ("time", UniformValue::Float(time)),
+            ("cursor", UniformValue::Vector(vec![inputs.cursor.x, inputs.cursor.y])),
+            ("keys_down", UniformValue::Float(inputs.keys_down)),
+            ("idle", UniformValue::Float(inputs.idle)),
+        ];
+        let (first, second) = self.targets.split_at_mut(1);
+        let (mut from, mut to) = (&mut first[0], &mut second[0]);
+        let last = self.passes.len() - 1;
+        for (index, pass) in self.passes.iter_mut().enumerate() {
+            for (name, value) in &automatic {
+                pass.set_declared(name, value);
+            }
+            pass.shader.set_uniform_current_texture("texture");
+            // Passes replace what is below them so the alpha they compute is kept
+            let states = RenderStates::new(
+                BlendMode::NONE,
+                Transform::IDENTITY,
+                None,
+                Some(&pass.shader),
+            );
+            {
+                let mut sprite = Sprite::with_texture(from.texture());
+                if index == last {
+                    target.set_view(&target.default_view().to_owned());
+                    sprite.set_position(position);
+                    target.draw_with_renderstates(&sprite, &states);
+                    return;
+                }
+                to.draw_with_renderstates(&sprite, &states);
+            }
+            to.display();
+            std::mem::swap(&mut from, &mut to);
+        }
+    }
+}
+
+impl Default for ShaderChain {
+    fn default() -> Self {
+        Self {
+            passes: Vec::new(),
+            targets: Vec::new(),
+            started: clock::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declares_uniform() {
+        let source = "uniform sampler2D texture;\n\
+                      // uniform float hidden;\n\
+                      uniform vec2 cursor, resolution;\n\
+                      uniform float weights[4];\n\
+                      float time;";
+        assert!(declares_uniform(source, "texture"));
+        assert!(declares_uniform(source, "resolution"));
+        assert!(declares_uniform(source, "weights"));
+        assert!(!declares_uniform(source, "hidden"));
+        assert!(!declares_uniform(source, "time"));
+        assert!(!declares_uniform(source, "vec2"));
+    }
+
+    #[test]
+    fn test_builtin_defaults_are_declared() {
+        let builtins = [
+            BuiltinShader::ChromaKey,
+            BuiltinShader::Crt,
+            BuiltinShader::Glow,
+            BuiltinShader::ColorGrade,
+        ];
+        for builtin in builtins {
+            let source = builtin_source(builtin);
+            assert!(declares_uniform(source, "texture"));
+            for (name, _) in builtin_defaults(builtin) {
+                assert!(declares_uniform(source, name), "{:?} {}", builtin, name);
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use image::{Rgba, RgbaImage};
+use sfml::graphics::{Color, FloatRect, Texture};
+use sfml::system::Vector2f;
+use std::borrow::Cow;
+
+use super::{Affine, DrawStates, Frame, Renderer, Shape, ShapeKind, SpriteDraw};
+use crate::config::BlendMode;
+
+/// Pixels of the target a draw may touch, `right` and `bottom` excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Clip {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+fn to_unit(color: Color) -> [f32; 4] {
+    [color.r, color.g, color.b, color.a].map(|channel| channel as f32 / 255.0)
+}
+
+/// The blend equations SFML sets up in OpenGL, on straight (not premultiplied) colors
+fn blend(dst: &mut Rgba<u8>, src: [f32; 4], mode: BlendMode) {
+    let d = dst.0.map(|channel| channel as f32 / 255.0);
+    let sa = src[3];
+    let out = match mode {
+        BlendMode::Alpha => [
+            src[0] * sa + d[0] * (1.0 - sa),
+            src[1] * sa + d[1] * (1.0 - sa),
+            src[2] * sa + d[2] * (1.0 - sa),
+            sa + d[3] * (1.0 - sa),
+        ],
+        BlendMode::Add => [
+            src[0] * sa + d[0],
+            src[1] * sa + d[1],
+            src[2] * sa + d[2],
+            sa + d[3],
+        ],
+        BlendMode::Multiply => [src[0] * d[0], src[1] * d[1], src[2] * d[2], sa * d[3]],
+    };
+    *dst = Rgba(out.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+}
+
+fn texel(image: &RgbaImage, x: i64, y: i64) -> [f32; 4] {
+    let x = x.clamp(0, image.width() as i64 - 1) as u32;
+    let y = y.clamp(0, image.height() as i64 - 1) as u32;
+    image.get_pixel(x, y).0.map(|channel| channel as f32 / 255.0)
+}
+
+/// Color of `image` at `u`, `v` in pixels, filtered like an SFML texture
+fn sample(image: &RgbaImage, u: f32, v: f32, smooth: bool) -> [f32; 4] {
+    if !smooth {
+        return texel(image, u.floor() as i64, v.floor() as i64);
+    }
+    let (x, y) = (u - 0.5, v - 0.5);
+    let (left, top) = (x.floor(), y.floor());
+    let (fx, fy) = (x - left, y - top);
+    let (left, top) = (left as i64, top as i64);
+    let corners = [
+        (texel(image, left, top), (1.0 - fx) * (1.0 - fy)),
+        (texel(image, left + 1, top), fx * (1.0 - fy)),
+        (texel(image, left, top + 1), (1.0 - fx) * fy),
+        (texel(image, left + 1, top + 1), fx * fy),
+    ];
+    let mut color = [0.0; 4];
+    for (pixel, weight) in corners {
+        for channel in 0..4 {
+            color[channel] += pixel[channel] * weight;
+        }
+    }
+    color
+}
+
+/// Reads a texture back from the GPU, for frames loaded for the SFML backend
+pub(crate) fn texture_pixels(texture: &Texture) -> Option<RgbaImage> {
+    let image = texture.copy_to_image()?;
+    let size = image.size();
+    RgbaImage::from_raw(size.x, size.y, image.pixel_data().to_vec())
+}
+
+/// Draws into an image in memory, without any display or GL.
+///
+/// Pixels are covered when their center is, which is what SFML's unsmoothed drawing does too.
+#[derive(Debug, Clone)]
+pub(crate) struct SoftwareRenderer {
+    image: RgbaImage,
+    view: Affine,
+    clip: Clip,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            image: RgbaImage::new(width, height),
+            view: Affine::identity(),
+            clip: Clip {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            },
+        }
+    }
+
+    /// What was drawn
+    pub fn into_image(self) -> RgbaImage {
+        self.image
+    }
+
+    /// Target pixels that `transform` may map the `min` to `max` box onto, within the viewport
+    fn covered(&self, transform: &Affine, min: (f32, f32), max: (f32, f32)) -> Clip {
+        let corners = [min, (max.0, min.1), (min.0, max.1), max].map(|p| transform.apply(p));
+        let xs = corners.map(|p| p.0);
+        let ys = corners.map(|p| p.1);
+        let low = |values: [f32; 4]| values.into_iter().fold(f32::MAX, f32::min).floor();
+        let high = |values: [f32; 4]| values.into_iter().fold(f32::MIN, f32::max).ceil();
+        let within = |value: f32, from: u32, to: u32| value.max(from as f32).min(to as f32) as u32;
+        let clip = self.clip;
+        Clip {
+            left: within(low(xs), clip.left, clip.right),
+            top: within(low(ys), clip.top, clip.bottom),
+            right: within(high(xs), clip.left, clip.right),
+            bottom: within(high(ys), clip.top, clip.bottom),
+        }
+    }
+
+    /// Blends `color_at` into every covered pixel, `color_at` gets points in `transform`'s space
+    fn fill<F>(
+        &mut self,
+        transform: &Affine,
+        min: (f32, f32),
+        max: (f32, f32),
+        blend_mode: BlendMode,
+        color_at: F,
+    ) where
+        F: Fn(f32, f32) -> Option<[f32; 4]>,
+    {
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let area = self.covered(transform, min, max);
+        for y in area.top..area.bottom {
+            for x in area.left..area.right {
+                let (u, v) = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+                if let Some(color) = color_at(u, v) {
+                    blend(self.image.get_pixel_mut(x, y), color, blend_mode);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn set_view(&mut self, scene: Vector2f, viewport: FloatRect) {
+        let (width, height) = (self.image.width() as f32, self.image.height() as f32);
+        let left = viewport.left * width;
+        let top = viewport.top * height;
+        let right = (viewport.left + viewport.width) * width;
+        let bottom = (viewport.top + viewport.height) * height;
+        let scale = ((right - left) / scene.x, (bottom - top) / scene.y);
+        self.view = Affine::sprite((left, top), (0.0, 0.0), scale, 0.0);
+        let clamp = |value: f32, high: u32| value.round().max(0.0).min(high as f32) as u32;
+        self.clip = Clip {
+            left: clamp(left, self.image.width()),
+            top: clamp(top, self.image.height()),
+            right: clamp(right, self.image.width()),
+            bottom: clamp(bottom, self.image.height()),
+        };
+    }
+
+    fn clear(&mut self, color: Color) {
+        for pixel in self.image.pixels_mut() {
+            *pixel = Rgba([color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    fn draw_sprite(&mut self, sprite: &SpriteDraw, states: &DrawStates) {
+        let (image, smooth) = match sprite.frame {
+            Frame::Image { image, smooth } => (Cow::Borrowed(image), *smooth),
+            // Read back from the GPU for this draw only, see `Renderer::draw_sprite`
+            Frame::Texture(texture) => match texture_pixels(texture) {
+                Some(image) => (Cow::Owned(image), texture.is_smooth()),
+                None => return,
+            },
+        };
+        let transform = self.view.combine(&states.transform).combine(&sprite.transform());
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let tint = to_unit(sprite.color);
+        self.fill(&transform, (0.0, 0.0), (width, height), states.blend, |u, v| {
+            if u < 0.0 || v < 0.0 || u >= width || v >= height {
+                return None;
+            }
+            let mut color = sample(&image, u, v, smooth);
+            for channel in 0..4 {
+                color[channel] *= tint[channel];
+            }
+            Some(color)
+        });
+    }
+
+    fn draw_shape(&mut self, shape: &Shape) {
+        let transform = self.view.combine(&shape.transform());
+        let thickness = shape.outline_thickness.max(0.0);
+        let (width, height) = (shape.size.x, shape.size.y);
+        let (fill, outline) = (to_unit(shape.fill), to_unit(shape.outline));
+        let min = (-thickness, -thickness);
+        let max = (width + thickness, height + thickness);
+        let kind = shape.kind;
+        self.fill(&transform, min, max, BlendMode::Alpha, |x, y| {
+            let (inside, within_outline) = match kind {
+                ShapeKind::Rectangle => (
+                    x >= 0.0 && y >= 0.0 && x < width && y < height,
+                    x >= min.0 && y >= min.1 && x < max.0 && y < max.1,
+                ),
+                ShapeKind::Circle => {
+                    let radius = width / 2.0;
+                    let distance = (x - radius).hypot(y - radius);
+                    (distance <= radius, distance <= radius + thickness)
+                }
+            };
+            if inside {
+                Some(fill)
+            } else if within_outline {
+                Some(outline)
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Nothing to show, the frame is read with `image`
+    fn present(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    fn frame(width: u32, height: u32, color: Rgba<u8>) -> Frame {
+        Frame::Image {
+            image: RgbaImage::from_pixel(width, height, color),
+            smooth: false,
+        }
+    }
+
+    fn states(blend: BlendMode) -> DrawStates {
+        DrawStates {
+            blend,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sprite_is_placed_like_sfml() {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        let frame = frame(2, 2, RED);
+        let mut sprite = SpriteDraw::new(&frame);
+        sprite.position = Vector2f::new(4.0, 4.0);
+        sprite.origin = Vector2f::new(1.0, 1.0);
+        sprite.scale = Vector2f::new(2.0, 1.0);
+        renderer.draw_sprite(&sprite, &Default::default());
+        let image = renderer.into_image();
+        assert_eq!(RED, *image.get_pixel(2, 3));
+        assert_eq!(RED, *image.get_pixel(5, 4));
+        assert_eq!(Rgba([0, 0, 0, 0]), *image.get_pixel(6, 4));
+        assert_eq!(Rgba([0, 0, 0, 0]), *image.get_pixel(4, 5));
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let mut renderer = SoftwareRenderer::new(3, 1);
+        renderer.clear(Color::rgb(100, 100, 100));
+        let frame = frame(1, 1, Rgba([200, 50, 255, 255]));
+        let mut sprite = SpriteDraw::new(&frame);
+        sprite.color = Color::rgba(255, 255, 255, 128);
+        renderer.draw_sprite(&sprite, &states(BlendMode::Alpha));
+        sprite.position.x = 1.0;
+        renderer.draw_sprite(&sprite, &states(BlendMode::Add));
+        sprite.position.x = 2.0;
+        renderer.draw_sprite(&sprite, &states(BlendMode::Multiply));
+        let image = renderer.into_image();
+        assert_eq!(Rgba([150, 75, 178, 255]), *image.get_pixel(0, 0));
+        assert_eq!(Rgba([200, 125, 228, 255]), *image.get_pixel(1, 0));
+        assert_eq!(Rgba([78, 20, 100, 128]), *image.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn test_view_scales_and_clips() {
+        let mut renderer = SoftwareRenderer::new(8, 4);
+        renderer.clear(Color::BLUE);
+        renderer.set_view(Vector2f::new(2.0, 2.0), FloatRect::new(0.25, 0.0, 0.5, 1.0));
+        let frame = frame(4, 4, RED);
+        let mut sprite = SpriteDraw::new(&frame);
+        sprite.position = Vector2f::new(-1.0, -1.0);
+        renderer.draw_sprite(&sprite, &Default::default());
+        let image = renderer.into_image();
+        assert_eq!(Rgba([0, 0, 255, 255]), *image.get_pixel(1, 2));
+        assert_eq!(RED, *image.get_pixel(2, 0));
+        assert_eq!(RED, *image.get_pixel(5, 3));
+        assert_eq!(Rgba([0, 0, 255, 255]), *image.get_pixel(6, 2));
+    }
+
+    #[test]
+    fn test_smooth_frames_are_filtered() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        assert_eq!([0.0, 0.0, 0.0, 1.0], sample(&image, 0.9, 0.5, false));
+        let mid = sample(&image, 1.0, 0.5, true);
+        assert!((mid[0] - 0.5).abs() < 1e-6);
+        let right = sample(&image, 1.9, 0.5, true);
+        assert!(right.iter().all(|channel| (channel - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_shape_outline_is_outside() {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        let mut shape = Shape::rectangle(Vector2f::new(4.0, 4.0));
+        shape.position = Vector2f::new(2.0, 2.0);
+        shape.fill = Color::TRANSPARENT;
+        shape.outline = Color::YELLOW;
+        shape.outline_thickness = 1.0;
+        renderer.draw_shape(&shape);
+        let image = renderer.into_image();
+        assert_eq!(Rgba([255, 255, 0, 255]), *image.get_pixel(1, 1));
+        assert_eq!(Rgba([255, 255, 0, 255]), *image.get_pixel(6, 3));
+        assert_eq!(Rgba([0, 0, 0, 0]), *image.get_pixel(2, 2));
+        assert_eq!(Rgba([0, 0, 0, 0]), *image.get_pixel(7, 7));
+    }
+}
@@ -0,0 +1,167 @@
+use image::RgbaImage;
+use std::fs::File;
+use std::io::{self, Write};
+
+use super::{RenderError, RenderResult};
+use crate::args::VideoFormat;
+
+/// Path that stands for standard output
+const STDOUT_PATH: &str = "-";
+
+/// Undoes the premultiplication that drawing onto a transparent canvas leaves in the colors.
+///
+/// With SFML's blending the color of a sprite drawn over nothing ends up multiplied by its
+/// alpha, while PNG and raw RGBA expect colors that aren't.
+pub(crate) fn straight_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for channel in 0..3 {
+            pixel[channel] = ((pixel[channel] as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// BT.601 studio range YCbCr, as y4m readers assume
+fn to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+    let cb = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+    let cr = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+    [y, cb, cr].map(|value| value.round().clamp(0.0, 255.0) as u8)
+}
+
+fn y4m_header(width: u32, height: u32, fps: u32) -> String {
+    format!("YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444\n", width, height, fps)
+}
+
+/// One frame in `format`, without the stream header.
+///
+/// y4m has no alpha, so transparent parts of the canvas come out black.
+fn encode_frame(image: &RgbaImage, format: VideoFormat) -> Vec<u8> {
+    match format {
+        VideoFormat::Rgba => {
+            let mut image = image.clone();
+            straight_alpha(&mut image);
+            image.into_raw()
+        }
+        VideoFormat::Y4m => {
+            let pixels = (image.width() * image.height()) as usize;
+            let mut frame = b"FRAME\n".to_vec();
+            frame.resize(frame.len() + pixels * 3, 0);
+            let (_, planes) = frame.split_at_mut(6);
+            let (luma, chroma) = planes.split_at_mut(pixels);
+            let (cb, cr) = chroma.split_at_mut(pixels);
+            for (index, pixel) in image.pixels().enumerate() {
+                let [y, u, v] = to_ycbcr(pixel[0], pixel[1], pixel[2]);
+                luma[index] = y;
+                cb[index] = u;
+                cr[index] = v;
+            }
+            frame
+        }
+    }
+}
+
+/// Writes frames as an uncompressed video stream to a file, a FIFO or standard output
+pub(crate) struct VideoWriter {
+    out: Box<dyn Write>,
+    format: VideoFormat,
+    fps: u32,
+    /// Size of the first frame, which every other frame must have too
+    size: Option<(u32, u32)>,
+}
+
+impl VideoWriter {
+    /// Opens `path`, `-` for standard output. Opening a FIFO waits for its reader.
+    pub fn open(path: &str, format: VideoFormat, fps: u32) -> RenderResult<Self> {
+        let out: Box<dyn Write> = if path == STDOUT_PATH {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Self {
+            out,
+            format,
+            fps: fps.max(1),
+            size: None,
+        })
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// Writes `image` `repeat` times, to keep the stream at its framerate when frames were missed
+    pub fn write_frame(&mut self, image: &RgbaImage, repeat: u32) -> RenderResult<()> {
+        let size = image.dimensions();
+        match self.size {
+            Some(expected) if expected != size => {
+                return Err(RenderError::FrameSize { expected, size });
+            }
+            Some(_) => {}
+            None => {
+                if self.format == VideoFormat::Y4m {
+                    let header = y4m_header(size.0, size.1, self.fps);
+                    self.write(header.as_bytes())?;
+                }
+                self.size = Some(size);
+            }
+        }
+        let frame = encode_frame(image, self.format);
+        for _ in 0..repeat {
+            self.write(&frame)?;
+        }
+        self.out.flush().map_err(closed_or_file)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> RenderResult<()> {
+        self.out.write_all(bytes).map_err(closed_or_file)
+    }
+}
+
+/// A reader that went away, such as ffmpeg quitting, ends the stream rather than failing it
+fn closed_or_file(err: io::Error) -> RenderError {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        RenderError::Closed
+    } else {
+        RenderError::File(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_straight_alpha() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([100, 50, 0, 128]));
+        image.put_pixel(1, 0, Rgba([10, 20, 30, 255]));
+        straight_alpha(&mut image);
+        assert_eq!(Rgba([199, 100, 0, 128]), *image.get_pixel(0, 0));
+        assert_eq!(Rgba([10, 20, 30, 255]), *image.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn test_y4m_frame() {
+        assert_eq!("YUV4MPEG2 W3 H1 F30:1 Ip A1:1 C444\n", y4m_header(3, 1, 30));
+        let mut image = RgbaImage::new(3, 1);
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 255]));
+        image.put_pixel(2, 0, Rgba([255, 0, 0, 255]));
+        let frame = encode_frame(&image, VideoFormat::Y4m);
+        assert_eq!(b"FRAME\n", &frame[..6]);
+        assert_eq!([235, 16, 82], frame[6..9]);
+        assert_eq!([128, 128, 90], frame[9..12]);
+        assert_eq!([128, 128, 240], frame[12..15]);
+    }
+
+    #[test]
+    fn test_rgba_frame_is_straight() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([50, 0, 0, 128]));
+        assert_eq!(vec![100, 0, 0, 128], encode_frame(&image, VideoFormat::Rgba));
+    }
+}
@@ -0,0 +1,20 @@
+use core::result;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("reading or writing session file")]
+    File(#[from] io::Error),
+    #[error("line {line}: {source}")]
+    Parse {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("line {line}: time has to be a positive number of seconds")]
+    InvalidTime { line: usize },
+    #[error("line {line}: unknown key {key:?}")]
+    UnknownKey { line: usize, key: String },
+}
+
+pub type SessionResult<T> = result::Result<T, SessionError>;
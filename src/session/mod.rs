@@ -0,0 +1,5 @@
+mod errors;
+mod timeline;
+
+pub(crate) use self::errors::{SessionError, SessionResult};
+pub(crate) use self::timeline::{Session, SessionEvent, SessionRecorder};
@@ -0,0 +1,261 @@
+use device_query::Keycode;
+use gilrs::Button;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sfml::system::Vector2f;
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{SessionError, SessionResult};
+use crate::models::{DeviceButton, DeviceType};
+use crate::user_input::{DeviceEvent, KeyboardEvent};
+use crate::view_models::{DeviceViewModelImpl, KeyboardViewModelImpl};
+
+/// One input event as written to a session file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// Cursor position, from 0 to 1 across the screen
+    MouseMoved { x: f32, y: f32 },
+    /// Gamepad stick, in the same range as the cursor
+    AxisXMoved { value: f32 },
+    AxisYMoved { value: f32 },
+    ButtonPressed { button: DeviceButton },
+    ButtonReleased { button: DeviceButton },
+    /// Keys are named like `device_query`'s `Keycode`, `A`, `Key1`, `Space`...
+    KeyPressed { key: String },
+    KeyReleased { key: String },
+    GamepadPressed { button: Button },
+    GamepadReleased { button: Button },
+    DeviceChanged { device: DeviceType },
+}
+
+/// A session event and when it happened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedEvent {
+    /// Seconds since the session started
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+/// An event as the view models take it
+enum Input {
+    Device(DeviceEvent),
+    Keyboard(KeyboardEvent),
+}
+
+/// The key called `name`, or the name back when there is no such key
+fn parse_key(name: &str) -> Result<Keycode, &str> {
+    Keycode::from_str(name).map_err(|_| name)
+}
+
+impl SessionEvent {
+    pub fn from_device(event: &DeviceEvent) -> Self {
+        match event {
+            DeviceEvent::ButtonPressed(button) => Self::ButtonPressed { button: *button },
+            DeviceEvent::ButtonReleased(button) => Self::ButtonReleased { button: *button },
+            DeviceEvent::MouseMoved(position) => Self::MouseMoved {
+                x: position.x,
+                y: position.y,
+            },
+            DeviceEvent::AxisXMoved(value) => Self::AxisXMoved { value: *value },
+            DeviceEvent::AxisYMoved(value) => Self::AxisYMoved { value: *value },
+            DeviceEvent::DeviceChanged(device) => Self::DeviceChanged { device: *device },
+        }
+    }
+
+    pub fn from_keyboard(event: &KeyboardEvent) -> Self {
+        match event {
+            KeyboardEvent::KeyPressed(key) => Self::KeyPressed {
+                key: format!("{:?}", key),
+            },
+            KeyboardEvent::KeyReleased(key) => Self::KeyReleased {
+                key: format!("{:?}", key),
+            },
+            KeyboardEvent::ButtonPressed(button) => Self::GamepadPressed { button: *button },
+            KeyboardEvent::ButtonReleased(button) => Self::GamepadReleased { button: *button },
+        }
+    }
+
+    /// The event for the view models, fails with the name of a key that isn't known
+    fn to_input(&self) -> Result<Input, &str> {
+        let input = match self {
+            Self::MouseMoved { x, y } => {
+                Input::Device(DeviceEvent::MouseMoved(Vector2f::new(*x, *y)))
+            }
+            Self::AxisXMoved { value } => Input::Device(DeviceEvent::AxisXMoved(*value)),
+            Self::AxisYMoved { value } => Input::Device(DeviceEvent::AxisYMoved(*value)),
+            Self::ButtonPressed { button } => Input::Device(DeviceEvent::ButtonPressed(*button)),
+            Self::ButtonReleased { button } => {
+                Input::Device(DeviceEvent::ButtonReleased(*button))
+            }
+            Self::KeyPressed { key } => Input::Keyboard(KeyboardEvent::KeyPressed(parse_key(key)?)),
+            Self::KeyReleased { key } => {
+                Input::Keyboard(KeyboardEvent::KeyReleased(parse_key(key)?))
+            }
+            Self::GamepadPressed { button } => {
+                Input::Keyboard(KeyboardEvent::ButtonPressed(*button))
+            }
+            Self::GamepadReleased { button } => {
+                Input::Keyboard(KeyboardEvent::ButtonReleased(*button))
+            }
+            Self::DeviceChanged { device } => Input::Device(DeviceEvent::DeviceChanged(*device)),
+        };
+        Ok(input)
+    }
+}
+
+/// A recorded input timeline, played back in order of time
+pub struct Session {
+    inputs: Vec<(Duration, Input)>,
+    next: usize,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> SessionResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads one JSON event per line, blank lines are skipped
+    pub fn parse(source: &str) -> SessionResult<Self> {
+        let mut inputs = Vec::new();
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let timed: TimedEvent = serde_json::from_str(text)
+                .map_err(|source| SessionError::Parse { line, source })?;
+            if !timed.time.is_finite() || timed.time < 0.0 {
+                return Err(SessionError::InvalidTime { line });
+            }
+            let input = timed
+                .event
+                .to_input()
+                .map_err(|key| SessionError::UnknownKey {
+                    line,
+                    key: key.to_string(),
+                })?;
+            inputs.push((Duration::from_secs_f64(timed.time), input));
+        }
+        // Stable, so events at the same time keep their order
+        inputs.sort_by_key(|(time, _)| *time);
+        Ok(Self { inputs, next: 0 })
+    }
+
+    /// Time of the last event
+    pub fn duration(&self) -> Duration {
+        self.inputs.last().map(|(time, _)| *time).unwrap_or_default()
+    }
+
+    /// Feeds every event up to `time` that wasn't played yet to the view models
+    pub fn play_until(
+        &mut self,
+        time: Duration,
+        keyboard: &KeyboardViewModelImpl,
+        mouse: &DeviceViewModelImpl,
+    ) {
+        while let Some((at, input)) = self.inputs.get(self.next) {
+            if *at > time {
+                break;
+            }
+            match input {
+                Input::Device(event) => mouse.handle_event(event),
+                Input::Keyboard(event) => keyboard.handle_event(event),
+            }
+            self.next += 1;
+        }
+    }
+}
+
+/// Writes live input to a session file as it happens
+pub struct SessionRecorder {
+    started: Instant,
+    out: Mutex<LineWriter<File>>,
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path) -> SessionResult<Self> {
+        Ok(Self {
+            started: Instant::now(),
+            out: Mutex::new(LineWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn record(&self, event: SessionEvent) {
+        let timed = TimedEvent {
+            time: self.started.elapsed().as_secs_f64(),
+            event,
+        };
+        let line = match serde_json::to_string(&timed) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("Could not record {:?}: {}", timed, err);
+                return;
+            }
+        };
+        if let Ok(mut out) = self.out.lock() {
+            if let Err(err) = writeln!(out, "{}", line) {
+                warn!("Could not write to the session file: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_format() {
+        let timed = TimedEvent {
+            time: 0.5,
+            event: SessionEvent::ButtonPressed {
+                button: DeviceButton::Left,
+            },
+        };
+        let line = serde_json::to_string(&timed).unwrap();
+        assert_eq!(r#"{"time":0.5,"type":"button_pressed","button":"left"}"#, line);
+        assert_eq!(timed, serde_json::from_str(&line).unwrap());
+
+        let moved: TimedEvent =
+            serde_json::from_str(r#"{"time": 1, "type": "mouse_moved", "x": 0.25, "y": 0.75}"#)
+                .unwrap();
+        assert_eq!(SessionEvent::MouseMoved { x: 0.25, y: 0.75 }, moved.event);
+    }
+
+    #[test]
+    fn test_parse_sorts_by_time() {
+        let session = Session::parse(
+            r#"{"time": 2.0, "type": "key_pressed", "key": "A"}
+
+{"time": 0.5, "type": "device_changed", "device": "gamepad"}
+"#,
+        )
+        .unwrap();
+        assert_eq!(2, session.inputs.len());
+        assert_eq!(Duration::from_secs(2), session.duration());
+        assert_eq!(Duration::from_millis(500), session.inputs[0].0);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let error = Session::parse("{\"time\": 0, \"type\": \"key_pressed\", \"key\": \"A\"}\nnope")
+            .err()
+            .unwrap();
+        assert!(matches!(error, SessionError::Parse { line: 2, .. }));
+        let error = Session::parse(r#"{"time": -1, "type": "mouse_moved", "x": 0, "y": 0}"#)
+            .err()
+            .unwrap();
+        assert!(matches!(error, SessionError::InvalidTime { line: 1 }));
+        let error = Session::parse(r#"{"time": 0, "type": "key_released", "key": "NoSuchKey"}"#)
+            .err()
+            .unwrap();
+        assert!(matches!(error, SessionError::UnknownKey { line: 1, .. }));
+    }
+}
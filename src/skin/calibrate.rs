@@ -0,0 +1,358 @@
+use image::{Rgba, RgbaImage};
+use log::info;
+use sfml::system::Vector2;
+use std::path::Path;
+
+use super::compose::{draw_image, draw_rect_outline, fill_circle};
+use super::{read_skin_image, SkinError, SkinResult};
+use crate::render::Affine;
+use crate::Config;
+
+const RIGHT_ARM_IMAGE: &str = "arm.png";
+const BACKGROUND_IMAGE: &str = "background.png";
+const AVATAR_IMAGE: &str = "avatar.png";
+const MOUSE_IMAGE: &str = "mouse.png";
+
+/// Pixels at or below this alpha count as empty space
+const ALPHA_THRESHOLD: u8 = 16;
+/// Rows above this fraction of the avatar's height are assumed to be the head
+const HEAD_FRACTION: f32 = 0.25;
+/// How wide a row has to be, relative to the widest row, to count as the shoulders
+const SHOULDER_WIDTH_FRACTION: f32 = 0.85;
+/// How far from the shoulder the hand rests, relative to the arm's length
+const REST_REACH_FRACTION: f32 = 0.6;
+const GAP: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Bounds {
+    pub fn width(&self) -> u32 {
+        self.right - self.left + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.bottom - self.top + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub anchor: (f32, f32),
+    pub arm_offset: (f32, f32),
+    pub mouse_mark_position: (f32, f32),
+    pub mouse_mark_size: (f32, f32),
+    pub mouse_mark_rotation: f32,
+}
+
+impl Calibration {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            anchor: (config.anchors.anchor.x.into(), config.anchors.anchor.y.into()),
+            arm_offset: (config.anchors.arm_offset.x.into(), config.anchors.arm_offset.y.into()),
+            mouse_mark_position: (config.mouse_mark.position.x.into(), config.mouse_mark.position.y.into()),
+            mouse_mark_size: (config.mouse_mark.size.x.into(), config.mouse_mark.size.y.into()),
+            mouse_mark_rotation: config.mouse_mark.rotation.into(),
+        }
+    }
+
+    pub fn apply_to(&self, config: &mut Config) {
+        let round = |(x, y): (f32, f32)| Vector2::new((x.round() as isize).into(), (y.round() as isize).into());
+        config.anchors.anchor = round(self.anchor);
+        config.anchors.arm_offset = round(self.arm_offset);
+        config.mouse_mark.position = round(self.mouse_mark_position);
+        config.mouse_mark.size = round(self.mouse_mark_size);
+        config.mouse_mark.rotation = ((self.mouse_mark_rotation * 10.0).round() / 10.0).into();
+    }
+
+    /// Maps points on the mouse pad, from its top left corner, onto the canvas
+    pub fn mouse_mark_transform(&self) -> Affine {
+        Affine::sprite(self.mouse_mark_position, (0.0, 0.0), (1.0, 1.0), self.mouse_mark_rotation)
+    }
+
+    /// Where the hand sits with the cursor in the middle of the screen
+    pub fn rest_hand_position(&self) -> (f32, f32) {
+        let (width, height) = self.mouse_mark_size;
+        self.mouse_mark_transform().apply((width / 2.0, height / 2.0))
+    }
+}
+
+fn is_opaque(pixel: &Rgba<u8>) -> bool {
+    pixel[3] > ALPHA_THRESHOLD
+}
+
+/// Smallest rectangle holding every non-transparent pixel
+pub fn opaque_bounds(image: &RgbaImage) -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if !is_opaque(pixel) {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => Bounds { left: x, top: y, right: x, bottom: y },
+            Some(b) => Bounds {
+                left: b.left.min(x),
+                top: b.top.min(y),
+                right: b.right.max(x),
+                bottom: b.bottom.max(y),
+            },
+        });
+    }
+    bounds
+}
+
+/// Leftmost and rightmost opaque pixel of a row
+fn row_extent(image: &RgbaImage, y: u32) -> Option<(u32, u32)> {
+    let mut opaque = (0..image.width()).filter(|x| is_opaque(image.get_pixel(*x, y)));
+    let first = opaque.next()?;
+    let last = opaque.last().unwrap_or(first);
+    Some((first, last))
+}
+
+/// Point on the arm image that gets pinned to the shoulder: the middle of its topmost opaque band
+pub fn arm_attachment(arm: &RgbaImage) -> Option<(f32, f32)> {
+    let bounds = opaque_bounds(arm)?;
+    let band = (bounds.height() / 10).max(1);
+    let (mut sum, mut count) = (0.0, 0.0);
+    for y in bounds.top..(bounds.top + band).min(bounds.bottom + 1) {
+        for x in bounds.left..=bounds.right {
+            if is_opaque(arm.get_pixel(x, y)) {
+                sum += x as f32 + 0.5;
+                count += 1.0;
+            }
+        }
+    }
+    if count == 0.0 {
+        return None;
+    }
+    Some((sum / count, bounds.top as f32))
+}
+
+/// Shoulder on the avatar's silhouette, on the side facing `toward_x`, pulled in by `inset`
+pub fn shoulder_pivot(avatar: &RgbaImage, toward_x: f32, inset: f32) -> Option<(f32, f32)> {
+    let bounds = opaque_bounds(avatar)?;
+    let first_row = bounds.top + (bounds.height() as f32 * HEAD_FRACTION) as u32;
+    let widths: Vec<(u32, u32, u32)> = (first_row..=bounds.bottom)
+        .filter_map(|y| row_extent(avatar, y).map(|(left, right)| (y, left, right)))
+        .collect();
+    let widest = widths.iter().map(|(_, left, right)| right - left).max()?;
+    let (y, left, right) = widths
+        .iter()
+        .find(|(_, left, right)| (right - left) as f32 >= widest as f32 * SHOULDER_WIDTH_FRACTION)?;
+    let center = (*left + *right) as f32 / 2.0;
+    let x = if toward_x < center {
+        *left as f32 + inset
+    } else {
+        *right as f32 - inset
+    };
+    Some((x, *y as f32))
+}
+
+/// Mouse pad placed within reach of the shoulder, sized for the mouse to travel across it
+pub fn mouse_pad(
+    anchor: (f32, f32),
+    arm_length: f32,
+    mouse_size: (f32, f32),
+    side: f32,
+    rotation: f32,
+) -> ((f32, f32), (f32, f32)) {
+    let reach = arm_length * REST_REACH_FRACTION;
+    let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+    let center = (anchor.0 + side.signum() * reach * diagonal, anchor.1 + reach * diagonal);
+    let size = (mouse_size.0 * 3.0, mouse_size.1 * 1.2);
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (half_w, half_h) = (size.0 / 2.0, size.1 / 2.0);
+    let position = (
+        center.0 - (cos * half_w - sin * half_h),
+        center.1 - (sin * half_w + cos * half_h),
+    );
+    (position, size)
+}
+
+fn propose(config: &Config, avatar: &RgbaImage, arm: &RgbaImage, mouse: &RgbaImage) -> Calibration {
+    let current = Calibration::from_config(config);
+    let mut proposed = current;
+    let arm_bounds = opaque_bounds(arm);
+    if let Some(offset) = arm_attachment(arm) {
+        proposed.arm_offset = offset;
+    }
+    let arm_width = arm_bounds.map(|b| b.width() as f32).unwrap_or(0.0);
+    let arm_length = arm.height() as f32;
+
+    let (pad_x, _) = current.rest_hand_position();
+    if let Some(anchor) = shoulder_pivot(avatar, pad_x, arm_width / 2.0) {
+        proposed.anchor = anchor;
+    }
+    let avatar_center = opaque_bounds(avatar)
+        .map(|b| (b.left + b.right) as f32 / 2.0)
+        .unwrap_or(avatar.width() as f32 / 2.0);
+    let mouse_scale: (f32, f32) = (config.mouse_scale.x.into(), config.mouse_scale.y.into());
+    let mouse_size = opaque_bounds(mouse)
+        .map(|b| (b.width() as f32 * mouse_scale.0, b.height() as f32 * mouse_scale.1))
+        .unwrap_or(current.mouse_mark_size);
+    let (position, size) = mouse_pad(
+        proposed.anchor,
+        arm_length,
+        mouse_size,
+        pad_x - avatar_center,
+        current.mouse_mark_rotation,
+    );
+    proposed.mouse_mark_position = position;
+    proposed.mouse_mark_size = size;
+    proposed
+}
+
+/// Stretches the arm from the shoulder to the resting hand
+fn arm_transform(calibration: &Calibration, arm: &RgbaImage) -> SkinResult<Affine> {
+    let hand = calibration.rest_hand_position();
+    let (dx, dy) = (hand.0 - calibration.anchor.0, hand.1 - calibration.anchor.1);
+    let dist = dx.hypot(dy);
+    if dist <= f32::EPSILON {
+        return Err(SkinError::HandOnShoulder);
+    }
+    Ok(Affine::sprite(
+        calibration.anchor,
+        calibration.arm_offset,
+        (1.0, dist / arm.height() as f32),
+        (-dx / dist).asin().to_degrees(),
+    ))
+}
+
+/// Draws the rig at rest with its debug marks, the way `Avatar::draw` would
+fn render_rig(
+    config: &Config,
+    calibration: &Calibration,
+    background: &RgbaImage,
+    avatar: &RgbaImage,
+    arm: &RgbaImage,
+    mouse: &RgbaImage,
+) -> SkinResult<RgbaImage> {
+    let mut canvas = RgbaImage::new(config.window.width, config.window.height);
+    let identity = Affine::identity();
+    draw_image(&mut canvas, background, &identity);
+
+    let hand = calibration.rest_hand_position();
+    let arm_transform = arm_transform(calibration, arm)?;
+    let mouse_scale = (config.mouse_scale.x.into(), config.mouse_scale.y.into());
+    let mouse_origin = (mouse.width() as f32 / 2.0, mouse.height() as f32 / 2.0);
+    let mouse_transform = Affine::sprite(hand, mouse_origin, mouse_scale, 0.0);
+
+    if config.avatar_below_arm {
+        draw_image(&mut canvas, avatar, &identity);
+        draw_image(&mut canvas, mouse, &mouse_transform);
+        draw_image(&mut canvas, arm, &arm_transform);
+    } else {
+        draw_image(&mut canvas, mouse, &mouse_transform);
+        draw_image(&mut canvas, arm, &arm_transform);
+        draw_image(&mut canvas, avatar, &identity);
+    }
+
+    fill_circle(&mut canvas, hand, 5.0, Rgba([255, 0, 0, 255]));
+    fill_circle(&mut canvas, calibration.anchor, 5.0, Rgba([0, 0, 255, 255]));
+    draw_rect_outline(
+        &mut canvas,
+        calibration.mouse_mark_size,
+        &calibration.mouse_mark_transform(),
+        Rgba([255, 255, 0, 255]),
+    );
+    Ok(canvas)
+}
+
+/// Proposes anchors and a mouse pad from the skin's alpha, saving them unless `dry_run` is set
+pub fn calibrate_skin(config: &mut Config, preview: &Path, dry_run: bool) -> SkinResult<Calibration> {
+    let skin_dir = config.images_path.clone();
+    let background = read_skin_image(&skin_dir, BACKGROUND_IMAGE)?;
+    let avatar = read_skin_image(&skin_dir, AVATAR_IMAGE)?;
+    let arm = read_skin_image(&skin_dir, RIGHT_ARM_IMAGE)?;
+    let mouse = read_skin_image(&skin_dir, MOUSE_IMAGE)?;
+
+    let current = Calibration::from_config(config);
+    let proposed = propose(config, &avatar, &arm, &mouse);
+    info!("Current calibration: {:?}", current);
+    info!("Proposed calibration: {:?}", proposed);
+
+    let before = render_rig(config, &current, &background, &avatar, &arm, &mouse)?;
+    let after = render_rig(config, &proposed, &background, &avatar, &arm, &mouse)?;
+    let mut comparison = RgbaImage::new(before.width() * 2 + GAP, before.height());
+    image::imageops::overlay(&mut comparison, &before, 0, 0);
+    image::imageops::overlay(&mut comparison, &after, (before.width() + GAP).into(), 0);
+    comparison.save(preview)?;
+    info!("Wrote before/after comparison to {:?}", preview);
+
+    if !dry_run {
+        proposed.apply_to(config);
+        config.save_calibration(&config.config_path)?;
+        info!("Saved calibration to {:?}", config.config_path);
+    }
+    Ok(proposed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_with_rect(width: u32, height: u32, rect: Bounds) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            if x >= rect.left && x <= rect.right && y >= rect.top && y <= rect.bottom {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn test_opaque_bounds() {
+        let rect = Bounds { left: 3, top: 5, right: 10, bottom: 7 };
+        let image = image_with_rect(20, 20, rect);
+        assert_eq!(Some(rect), opaque_bounds(&image));
+        assert_eq!(None, opaque_bounds(&RgbaImage::new(4, 4)));
+    }
+
+    #[test]
+    fn test_arm_attachment_is_top_center() {
+        let image = image_with_rect(40, 100, Bounds { left: 10, top: 2, right: 29, bottom: 90 });
+        assert_eq!(Some((20.0, 2.0)), arm_attachment(&image));
+    }
+
+    #[test]
+    fn test_shoulder_pivot_picks_side() {
+        // Narrow head on top of a wide body
+        let mut image = image_with_rect(100, 100, Bounds { left: 20, top: 50, right: 79, bottom: 99 });
+        for y in 0..50 {
+            for x in 40..60 {
+                image.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        assert_eq!(Some((25.0, 50.0)), shoulder_pivot(&image, 0.0, 5.0));
+        assert_eq!(Some((74.0, 50.0)), shoulder_pivot(&image, 100.0, 5.0));
+    }
+
+    #[test]
+    fn test_hand_on_shoulder_is_an_error() {
+        let calibration = Calibration {
+            anchor: (0.0, 0.0),
+            arm_offset: (0.0, 0.0),
+            mouse_mark_position: (0.0, 0.0),
+            mouse_mark_size: (0.0, 0.0),
+            mouse_mark_rotation: 0.0,
+        };
+        let arm = RgbaImage::new(4, 4);
+        assert!(matches!(arm_transform(&calibration, &arm), Err(SkinError::HandOnShoulder)));
+    }
+
+    #[test]
+    fn test_mouse_pad_is_centered_within_reach() {
+        let (position, size) = mouse_pad((100.0, 100.0), 100.0, (10.0, 10.0), -1.0, 0.0);
+        assert_eq!((30.0, 12.0), size);
+        let center = (position.0 + size.0 / 2.0, position.1 + size.1 / 2.0);
+        let reach = (center.0 - 100.0).hypot(center.1 - 100.0);
+        assert!((reach - 60.0).abs() < 1e-3);
+        assert!(center.0 < 100.0);
+    }
+}
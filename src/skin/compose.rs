@@ -0,0 +1,92 @@
+use image::{Rgba, RgbaImage};
+
+use crate::render::Affine;
+
+fn blend(canvas: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= canvas.width() as i64 || y >= canvas.height() as i64 {
+        return;
+    }
+    let alpha = color[3] as f32 / 255.0;
+    if alpha <= 0.0 {
+        return;
+    }
+    let dst = canvas.get_pixel_mut(x as u32, y as u32);
+    let dst_alpha = dst[3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    for channel in 0..3 {
+        let value = (color[channel] as f32 * alpha
+            + dst[channel] as f32 * dst_alpha * (1.0 - alpha))
+            / out_alpha;
+        dst[channel] = value.round() as u8;
+    }
+    dst[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// Alpha blends `image` onto `canvas` through `transform`, sampling nearest pixels
+pub fn draw_image(canvas: &mut RgbaImage, image: &RgbaImage, transform: &Affine) {
+    let inverse = match transform.inverse() {
+        Some(inverse) => inverse,
+        None => return,
+    };
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)].map(|p| transform.apply(p));
+    let min_x = corners.iter().map(|p| p.0).fold(f32::MAX, f32::min).floor().max(0.0) as u32;
+    let min_y = corners.iter().map(|p| p.1).fold(f32::MAX, f32::min).floor().max(0.0) as u32;
+    let max_x = corners.iter().map(|p| p.0).fold(f32::MIN, f32::max).ceil().min(canvas.width() as f32) as u32;
+    let max_y = corners.iter().map(|p| p.1).fold(f32::MIN, f32::max).ceil().min(canvas.height() as f32) as u32;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let (u, v) = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+            if u >= 0.0 && v >= 0.0 && u < width && v < height {
+                let color = *image.get_pixel(u as u32, v as u32);
+                blend(canvas, x as i64, y as i64, color);
+            }
+        }
+    }
+}
+
+pub fn fill_circle(canvas: &mut RgbaImage, center: (f32, f32), radius: f32, color: Rgba<u8>) {
+    let reach = radius.ceil() as i64;
+    let (cx, cy) = (center.0.round() as i64, center.1.round() as i64);
+    for y in cy - reach..=cy + reach {
+        for x in cx - reach..=cx + reach {
+            let (dx, dy) = (x as f32 - center.0, y as f32 - center.1);
+            if dx * dx + dy * dy <= radius * radius {
+                blend(canvas, x, y, color);
+            }
+        }
+    }
+}
+
+pub fn draw_line(canvas: &mut RgbaImage, from: (f32, f32), to: (f32, f32), thickness: f32, color: Rgba<u8>) {
+    let length = (to.0 - from.0).hypot(to.1 - from.1);
+    let steps = length.ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f32 / steps as f32;
+        let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        fill_circle(canvas, point, thickness / 2.0, color);
+    }
+}
+
+/// Outline of a `size` rectangle drawn through `transform`, like the debug `mouse_mark`
+pub fn draw_rect_outline(canvas: &mut RgbaImage, size: (f32, f32), transform: &Affine, color: Rgba<u8>) {
+    let corners = [(0.0, 0.0), (size.0, 0.0), (size.0, size.1), (0.0, size.1)].map(|p| transform.apply(p));
+    for index in 0..corners.len() {
+        let next = corners[(index + 1) % corners.len()];
+        draw_line(canvas, corners[index], next, 2.0, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_image_identity() {
+        let mut canvas = RgbaImage::new(4, 4);
+        let image = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        draw_image(&mut canvas, &image, &Affine::sprite((1.0, 1.0), (0.0, 0.0), (1.0, 1.0), 0.0));
+        assert_eq!(Rgba([255, 0, 0, 255]), *canvas.get_pixel(1, 1));
+        assert_eq!(Rgba([0, 0, 0, 0]), *canvas.get_pixel(3, 3));
+    }
+}
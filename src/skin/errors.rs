@@ -0,0 +1,24 @@
+use crate::avatar::SfmlError;
+use crate::config::ConfigError;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SkinError {
+    #[error("filesystem")]
+    File(#[from] io::Error),
+    #[error("config")]
+    Config(#[from] ConfigError),
+    #[error("image")]
+    Image(#[from] image::ImageError),
+    #[error("decode")]
+    Decode(#[from] SfmlError),
+    #[error("refusing to overwrite {0:?}, pass --force to replace it")]
+    AlreadyExists(PathBuf),
+    #[error("the resting hand is on the shoulder, the arm can't reach it")]
+    HandOnShoulder,
+}
+
+pub type SkinResult<T> = result::Result<T, SkinError>;
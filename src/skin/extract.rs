@@ -0,0 +1,32 @@
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use super::{DefaultSkin, SkinError, SkinResult};
+use crate::{Config, DEFAULT_CONFIG_NAME};
+
+/// Writes the built-in skin and a matching default config into `dir`
+pub fn extract_default_skin(dir: &Path, force: bool) -> SkinResult<()> {
+    fs::create_dir_all(dir)?;
+
+    let config_path = dir.join(DEFAULT_CONFIG_NAME);
+    let mut files: Vec<_> = DefaultSkin::iter().map(|name| dir.join(name.as_ref())).collect();
+    files.push(config_path.clone());
+    if !force {
+        if let Some(existing) = files.into_iter().find(|path| path.exists()) {
+            return Err(SkinError::AlreadyExists(existing));
+        }
+    }
+
+    for name in DefaultSkin::iter() {
+        if let Some(file) = DefaultSkin::get(&name) {
+            let path = dir.join(name.as_ref());
+            info!("Writing {:?}", path);
+            fs::write(&path, file.data)?;
+        }
+    }
+
+    info!("Writing {:?}", config_path);
+    Config::default().save_to_file(&config_path)?;
+    Ok(())
+}
@@ -0,0 +1,37 @@
+mod calibrate;
+mod compose;
+mod errors;
+mod extract;
+mod optimize;
+
+use image::RgbaImage;
+use log::warn;
+use rust_embed::RustEmbed;
+use std::fs;
+use std::path::Path;
+
+pub(crate) use self::calibrate::{calibrate_skin, Calibration};
+pub(crate) use self::errors::{SkinError, SkinResult};
+pub(crate) use self::extract::extract_default_skin;
+pub(crate) use self::optimize::optimize_skin;
+
+/// Skin compiled into the binary, used for any part missing from the skin directory
+#[derive(RustEmbed)]
+#[folder = "assets/default_skin/"]
+#[include = "*.png"]
+pub(crate) struct DefaultSkin;
+
+/// Reads the first frame of a skin image, falling back to the built-in skin like the renderer does
+pub(crate) fn read_skin_image(skin_dir: &Path, image: &str) -> SkinResult<RgbaImage> {
+    let path = skin_dir.join(image);
+    let bytes = match DefaultSkin::get(image) {
+        Some(embedded) if !path.exists() => {
+            if skin_dir.is_dir() {
+                warn!("No {}, using the built-in {}", path.display(), image);
+            }
+            embedded.data.into_owned()
+        }
+        _ => fs::read(&path)?,
+    };
+    Ok(image::load_from_memory(&bytes)?.into_rgba8())
+}
@@ -0,0 +1,249 @@
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use log::info;
+use sfml::system::Vector2;
+use std::fs;
+use std::path::Path;
+
+use super::SkinResult;
+use crate::avatar::{decode_frames, part_name, skin_images, TextureSource};
+use crate::config::{CanvasSize, IntOrFloat, PartSettings};
+use crate::{Config, DEFAULT_CONFIG_NAME};
+
+const BACKGROUND_IMAGE: &str = "background.png";
+const AVATAR_IMAGE: &str = "avatar.png";
+
+#[derive(Debug, Default)]
+pub struct OptimizeReport {
+    /// Texture memory of the original images, in bytes
+    pub before: usize,
+    /// Texture memory of the optimized images, in bytes
+    pub after: usize,
+    /// Images copied unchanged because they are animated
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Smallest rectangle holding every pixel that isn't fully transparent
+pub fn visible_bounds(image: &RgbaImage) -> Crop {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] != 0 {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+    if left > right {
+        // Nothing visible, keep a single pixel so the part still loads
+        return Crop { left: 0, top: 0, width: 1, height: 1 };
+    }
+    Crop {
+        left,
+        top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    }
+}
+
+/// Factor that shrinks a `canvas` sized skin to fit inside `window`, never enlarging it
+pub fn fit_scale(canvas: (f32, f32), window: (f32, f32)) -> f32 {
+    (window.0 / canvas.0).min(window.1 / canvas.1).min(1.0)
+}
+
+fn to_f32(vector: &Vector2<IntOrFloat>) -> (f32, f32) {
+    (vector.x.into(), vector.y.into())
+}
+
+fn scale_vector(vector: &mut Vector2<IntOrFloat>, factor: f32) {
+    let (x, y) = to_f32(vector);
+    *vector = Vector2::new((x * factor).into(), (y * factor).into());
+}
+
+fn canvas_size(image: &RgbaImage, settings: &PartSettings) -> (f32, f32) {
+    let scale = to_f32(&settings.scale);
+    match settings.canvas {
+        Some(canvas) => (canvas.width, canvas.height),
+        None => (image.width() as f32 * scale.0, image.height() as f32 * scale.1),
+    }
+}
+
+/// Settings that draw `crop` of an image resized by `factor` exactly where the original was drawn
+pub fn optimized_settings(settings: &PartSettings, canvas: (f32, f32), factor: f32, crop: &Crop) -> PartSettings {
+    let (offset_x, offset_y) = to_f32(&settings.offset);
+    let (scale_x, scale_y) = to_f32(&settings.scale);
+    let mut optimized = settings.clone();
+    optimized.offset = Vector2::new(
+        (offset_x * factor + crop.left as f32 * scale_x).into(),
+        (offset_y * factor + crop.top as f32 * scale_y).into(),
+    );
+    optimized.canvas = Some(CanvasSize {
+        width: canvas.0 * factor,
+        height: canvas.1 * factor,
+    });
+    optimized
+}
+
+/// Settings that draw an image kept at its size where it was drawn before scaling by `factor`
+pub fn rescaled_settings(settings: &PartSettings, canvas: (f32, f32), factor: f32) -> PartSettings {
+    let mut rescaled = settings.clone();
+    scale_vector(&mut rescaled.offset, factor);
+    scale_vector(&mut rescaled.scale, factor);
+    rescaled.canvas = Some(CanvasSize {
+        width: canvas.0 * factor,
+        height: canvas.1 * factor,
+    });
+    rescaled
+}
+
+/// Fits the skin into the window, trims transparent borders and writes the result to `out_dir`
+pub fn optimize_skin(config: &Config, out_dir: &Path) -> SkinResult<OptimizeReport> {
+    let skin_dir = &config.images_path;
+    fs::create_dir_all(out_dir)?;
+    let mut report = OptimizeReport::default();
+    let mut optimized_config = config.clone();
+
+    let canvas = [BACKGROUND_IMAGE, AVATAR_IMAGE]
+        .iter()
+        .map(|image| (image, skin_dir.join(image)))
+        .find(|(_, path)| path.exists())
+        .map(|(image, path)| {
+            let settings = config.part_settings(part_name(image));
+            image::open(path).map(|source| canvas_size(&source.into_rgba8(), &settings))
+        })
+        .transpose()?;
+    let factor = canvas
+        .map(|canvas| {
+            fit_scale(
+                canvas,
+                (config.window.width as f32, config.window.height as f32),
+            )
+        })
+        .unwrap_or(1.0);
+    info!("Scaling skin by {}", factor);
+    scale_vector(&mut optimized_config.anchors.anchor, factor);
+    scale_vector(&mut optimized_config.anchors.arm_offset, factor);
+    scale_vector(&mut optimized_config.mouse_mark.position, factor);
+    scale_vector(&mut optimized_config.mouse_mark.size, factor);
+
+    let mut written = Vec::new();
+    for image in skin_images(config) {
+        let path = match TextureSource::resolve(skin_dir, &image) {
+            TextureSource::File(path) if path.exists() => path,
+            // Built-in parts aren't part of the skin, leave them out
+            _ => continue,
+        };
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name.to_owned(),
+            None => continue,
+        };
+        let frames = decode_frames(&fs::read(&path)?)?;
+        let frame_bytes: usize = frames.iter().map(|frame| frame.image.as_raw().len()).sum();
+        report.before += frame_bytes;
+        written.push(file_name.clone());
+
+        let original = &frames[0].image;
+        let name = part_name(&image);
+        let settings = config.part_settings(name);
+        let canvas = canvas_size(original, &settings);
+        if frames.len() > 1 {
+            report.after += frame_bytes;
+            report.skipped.push(file_name.to_string_lossy().into_owned());
+            fs::copy(&path, out_dir.join(&file_name))?;
+            optimized_config
+                .parts
+                .insert(name.to_string(), rescaled_settings(&settings, canvas, factor));
+            continue;
+        }
+
+        let resized = if factor < 1.0 {
+            let width = ((original.width() as f32 * factor).round() as u32).max(1);
+            let height = ((original.height() as f32 * factor).round() as u32).max(1);
+            imageops::resize(original, width, height, FilterType::Lanczos3)
+        } else {
+            original.clone()
+        };
+        let crop = visible_bounds(&resized);
+        let trimmed = imageops::crop_imm(&resized, crop.left, crop.top, crop.width, crop.height).to_image();
+        report.after += trimmed.as_raw().len();
+        info!(
+            "{}: {}x{} -> {}x{}",
+            image,
+            original.width(),
+            original.height(),
+            trimmed.width(),
+            trimmed.height()
+        );
+        trimmed.save(out_dir.join(&file_name))?;
+        optimized_config
+            .parts
+            .insert(name.to_string(), optimized_settings(&settings, canvas, factor, &crop));
+    }
+
+    // Carry over anything else in the skin so the output is complete
+    for entry in fs::read_dir(skin_dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let is_config = config.config_path.file_name() == Some(file_name.as_os_str());
+        if entry.file_type()?.is_file() && !is_config && !written.contains(&file_name) {
+            fs::copy(entry.path(), out_dir.join(&file_name))?;
+        }
+    }
+    optimized_config.save_to_file(&out_dir.join(DEFAULT_CONFIG_NAME))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_visible_bounds() {
+        let mut image = RgbaImage::new(10, 10);
+        image.put_pixel(2, 3, Rgba([0, 0, 0, 1]));
+        image.put_pixel(6, 8, Rgba([0, 0, 0, 255]));
+        assert_eq!(Crop { left: 2, top: 3, width: 5, height: 6 }, visible_bounds(&image));
+    }
+
+    #[test]
+    fn test_visible_bounds_empty_image() {
+        let image = RgbaImage::new(10, 10);
+        assert_eq!(Crop { left: 0, top: 0, width: 1, height: 1 }, visible_bounds(&image));
+    }
+
+    #[test]
+    fn test_fit_scale() {
+        assert_eq!(0.5, fit_scale((1224.0, 934.0), (612.0, 467.0)));
+        assert_eq!(1.0, fit_scale((300.0, 200.0), (612.0, 467.0)));
+    }
+
+    #[test]
+    fn test_optimized_settings_keep_position() {
+        let settings = PartSettings::default();
+        let crop = Crop { left: 10, top: 20, width: 5, height: 5 };
+        let optimized = optimized_settings(&settings, (100.0, 80.0), 0.5, &crop);
+        assert_eq!((10.0, 20.0), to_f32(&optimized.offset));
+        assert_eq!(Some(CanvasSize { width: 50.0, height: 40.0 }), optimized.canvas);
+    }
+
+    #[test]
+    fn test_rescaled_settings_shrink_in_place() {
+        let settings = PartSettings {
+            offset: Vector2::new(40.into(), 20.into()),
+            ..Default::default()
+        };
+        let rescaled = rescaled_settings(&settings, (100.0, 80.0), 0.5);
+        assert_eq!((20.0, 10.0), to_f32(&rescaled.offset));
+        assert_eq!((0.5, 0.5), to_f32(&rescaled.scale));
+        assert_eq!(Some(CanvasSize { width: 50.0, height: 40.0 }), rescaled.canvas);
+    }
+}
@@ -0,0 +1,10 @@
+use core::result;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error("listening on {address}: {message}")]
+    Bind { address: String, message: String },
+}
+
+pub type StreamResult<T> = result::Result<T, StreamError>;
@@ -0,0 +1,5 @@
+mod errors;
+mod server;
+
+pub(crate) use self::errors::{StreamError, StreamResult};
+pub(crate) use self::server::StreamServer;
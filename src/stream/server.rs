@@ -0,0 +1,474 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ColorType, DynamicImage, ImageEncoder, RgbaImage};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use super::{StreamError, StreamResult};
+use crate::avatar::ClipAction;
+use crate::config::StreamSettings;
+use crate::render::straight_alpha;
+
+/// Separates the JPEGs of the MJPEG stream
+const BOUNDARY: &str = "ptuberframe";
+/// How long a PNG request waits for a frame to be drawn
+const FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often a stream sends its last frame again while nothing new is drawn, so a viewer
+/// that disconnected shows up as a failed write
+const HEARTBEAT: Duration = Duration::from_secs(1);
+/// A stream that took no data for this long is stuck on a viewer that stopped reading
+const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const INDEX_PAGE: &str = "<!DOCTYPE html>\n<html><head><title>ptuber</title></head>\
+<body style=\"margin:0;background:transparent\"><img src=\"/stream.mjpg\"></body></html>\n";
+
+/// The latest frame, shared between the drawing loop and the viewers
+#[derive(Default, Clone)]
+struct Latest {
+    image: Option<Arc<RgbaImage>>,
+    /// Only encoded while a stream is watched
+    jpeg: Option<Arc<Vec<u8>>>,
+    /// Counts published frames, so viewers can wait for a new one
+    serial: u64,
+}
+
+struct Shared {
+    latest: Mutex<Latest>,
+    published: Condvar,
+    /// When each open MJPEG stream last asked for data, by stream id
+    streams: Mutex<HashMap<u64, Instant>>,
+    next_stream: AtomicU64,
+    /// PNG requests waiting for a frame
+    waiting: AtomicUsize,
+    /// Requests being answered
+    clients: AtomicUsize,
+    max_clients: usize,
+    quality: u8,
+    /// Where the control endpoints send screenshot and replay requests
+    control: Mutex<Option<Sender<ClipAction>>>,
+}
+
+impl Shared {
+    fn new(settings: &StreamSettings, control: Option<Sender<ClipAction>>) -> Self {
+        Self {
+            latest: Default::default(),
+            published: Condvar::new(),
+            streams: Default::default(),
+            next_stream: AtomicU64::new(0),
+            waiting: AtomicUsize::new(0),
+            clients: AtomicUsize::new(0),
+            max_clients: settings.max_clients.max(1),
+            quality: settings.quality.clamp(1, 100),
+            control: Mutex::new(control),
+        }
+    }
+
+    /// Takes a slot for a request, false when `max_clients` are already being answered
+    fn admit(&self) -> bool {
+        if self.clients.fetch_add(1, Ordering::SeqCst) < self.max_clients {
+            return true;
+        }
+        self.clients.fetch_sub(1, Ordering::SeqCst);
+        false
+    }
+
+    fn leave(&self) {
+        self.clients.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Records that stream `id` is keeping up with what it is sent
+    fn touch_stream(&self, id: u64) {
+        if let Ok(mut streams) = self.streams.lock() {
+            streams.insert(id, Instant::now());
+        }
+    }
+
+    fn close_stream(&self, id: u64) {
+        if let Ok(mut streams) = self.streams.lock() {
+            streams.remove(&id);
+        }
+    }
+
+    /// Open streams whose viewers still read what they are sent
+    fn active_streams(&self) -> usize {
+        let now = Instant::now();
+        self.streams
+            .lock()
+            .map(|streams| {
+                streams
+                    .values()
+                    .filter(|last| now.saturating_duration_since(**last) < STALL_TIMEOUT)
+                    .count()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Passes `action` on, false when nothing takes it
+    fn request(&self, action: ClipAction) -> bool {
+        match self.control.lock() {
+            Ok(control) => control
+                .as_ref()
+                .is_some_and(|control| control.send(action).is_ok()),
+            Err(_) => false,
+        }
+    }
+
+    /// Waits until a frame newer than `serial` is published, `None` on timeout
+    fn wait_newer(&self, serial: u64, timeout: Option<Duration>) -> Option<Latest> {
+        let latest = self.latest.lock().ok()?;
+        let unchanged = |latest: &mut Latest| latest.serial == serial;
+        let latest = match timeout {
+            Some(timeout) => {
+                let (latest, result) = self
+                    .published
+                    .wait_timeout_while(latest, timeout, unchanged)
+                    .ok()?;
+                if result.timed_out() {
+                    return None;
+                }
+                latest
+            }
+            None => self.published.wait_while(latest, unchanged).ok()?,
+        };
+        Some(latest.clone())
+    }
+
+    fn serial(&self) -> u64 {
+        self.latest.lock().map(|latest| latest.serial).unwrap_or_default()
+    }
+}
+
+/// One part of a `multipart/x-mixed-replace` stream
+fn mjpeg_part(jpeg: &[u8]) -> Vec<u8> {
+    let mut part = format!(
+        "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        BOUNDARY,
+        jpeg.len()
+    )
+    .into_bytes();
+    part.extend_from_slice(jpeg);
+    part.extend_from_slice(b"\r\n");
+    part
+}
+
+/// The body of an MJPEG response, producing a part for every published frame
+struct MjpegBody {
+    shared: Arc<Shared>,
+    id: u64,
+    serial: u64,
+    part: Vec<u8>,
+    sent: usize,
+}
+
+impl MjpegBody {
+    fn new(shared: Arc<Shared>) -> Self {
+        let id = shared.next_stream.fetch_add(1, Ordering::SeqCst);
+        shared.touch_stream(id);
+        Self {
+            id,
+            serial: shared.serial(),
+            shared,
+            part: Vec::new(),
+            sent: 0,
+        }
+    }
+}
+
+impl Read for MjpegBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.sent == self.part.len() {
+            // Being asked for more means the last part was written out
+            self.shared.touch_stream(self.id);
+            match self.shared.wait_newer(self.serial, Some(HEARTBEAT)) {
+                Some(latest) => {
+                    self.serial = latest.serial;
+                    // Frames published before this stream was counted have no JPEG yet
+                    if let Some(jpeg) = latest.jpeg {
+                        self.part = mjpeg_part(&jpeg);
+                        self.sent = 0;
+                    }
+                }
+                None if self.shared.latest.is_poisoned() => {
+                    let message = "stream state poisoned";
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe, message));
+                }
+                None if !self.part.is_empty() => self.sent = 0,
+                None => {}
+            }
+        }
+        let count = buf.len().min(self.part.len() - self.sent);
+        buf[..count].copy_from_slice(&self.part[self.sent..self.sent + count]);
+        self.sent += count;
+        Ok(count)
+    }
+}
+
+impl Drop for MjpegBody {
+    fn drop(&mut self) {
+        self.shared.close_stream(self.id);
+        debug!("MJPEG viewer left");
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
+
+fn encode_png(image: &RgbaImage) -> image::ImageResult<Vec<u8>> {
+    let mut image = image.clone();
+    straight_alpha(&mut image);
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).write_image(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        ColorType::Rgba8,
+    )?;
+    Ok(png)
+}
+
+fn respond_png(request: Request, shared: &Shared) -> io::Result<()> {
+    shared.waiting.fetch_add(1, Ordering::SeqCst);
+    let latest = shared.wait_newer(shared.serial(), Some(FRAME_TIMEOUT));
+    shared.waiting.fetch_sub(1, Ordering::SeqCst);
+    let png = latest.and_then(|latest| latest.image).map(|image| encode_png(&image));
+    match png {
+        Some(Ok(png)) => {
+            let content_type = header("Content-Type", "image/png");
+            request.respond(Response::from_data(png).with_header(content_type))
+        }
+        Some(Err(err)) => {
+            warn!("Could not encode a PNG frame: {}", err);
+            request.respond(Response::empty(StatusCode(500)))
+        }
+        None => request.respond(Response::empty(StatusCode(503))),
+    }
+}
+
+fn handle(request: Request, shared: Arc<Shared>) -> io::Result<()> {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    debug!("Stream request for {}", path);
+    match path.as_str() {
+        "/" => request.respond(
+            Response::from_string(INDEX_PAGE).with_header(header("Content-Type", "text/html")),
+        ),
+        "/stream.mjpg" => {
+            let content_type = format!("multipart/x-mixed-replace; boundary={}", BOUNDARY);
+            let response = Response::new(
+                StatusCode(200),
+                vec![
+                    header("Content-Type", &content_type),
+                    header("Cache-Control", "no-cache"),
+                ],
+                MjpegBody::new(shared),
+                None,
+                None,
+            );
+            request.respond(response)
+        }
+        "/frame.png" => respond_png(request, &shared),
+        "/screenshot" | "/replay" => {
+            let action = if path == "/screenshot" {
+                ClipAction::Screenshot
+            } else {
+                ClipAction::Replay
+            };
+            let status = if *request.method() != Method::Post {
+                405
+            } else if shared.request(action) {
+                202
+            } else {
+                503
+            };
+            request.respond(Response::empty(StatusCode(status)))
+        }
+        _ => request.respond(Response::empty(StatusCode(404))),
+    }
+}
+
+/// Serves the scene over HTTP: an MJPEG stream at `/stream.mjpg`, the next frame as PNG at
+/// `/frame.png` and a page showing the stream at `/`. `POST /screenshot` and `POST /replay`
+/// save a screenshot or the replay like the hotkeys do.
+pub(crate) struct StreamServer {
+    shared: Arc<Shared>,
+}
+
+impl StreamServer {
+    /// Starts listening, requests are answered on their own threads
+    pub fn start(
+        settings: &StreamSettings,
+        control: Option<Sender<ClipAction>>,
+    ) -> StreamResult<Self> {
+        let address = format!("{}:{}", settings.address, settings.port);
+        let server = Server::http(&address).map_err(|err| StreamError::Bind {
+            address: address.clone(),
+            message: err.to_string(),
+        })?;
+        info!("Streaming on http://{}/stream.mjpg", address);
+        let shared = Arc::new(Shared::new(settings, control));
+        let listener = shared.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if !listener.admit() {
+                    debug!("Turning a stream viewer away, {} are served", listener.max_clients);
+                    let _ = request.respond(Response::empty(StatusCode(503)));
+                    continue;
+                }
+                let shared = listener.clone();
+                thread::spawn(move || {
+                    if let Err(err) = handle(request, shared.clone()) {
+                        debug!("Stream viewer went away: {}", err);
+                    }
+                    shared.leave();
+                });
+            }
+        });
+        Ok(Self { shared })
+    }
+
+    /// Whether anyone is waiting for frames, nothing needs to be drawn otherwise
+    pub fn wanted(&self) -> bool {
+        self.shared.active_streams() + self.shared.waiting.load(Ordering::SeqCst) > 0
+    }
+
+    /// Hands a frame to the viewers, encoding it only if a stream is open
+    pub fn publish(&self, image: RgbaImage) {
+        let jpeg = if self.shared.active_streams() > 0 {
+            let mut jpeg = Vec::new();
+            // JPEG has no alpha, transparent parts come out black
+            let rgb = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+            let encoded = JpegEncoder::new_with_quality(&mut jpeg, self.shared.quality).encode(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            );
+            match encoded {
+                Ok(()) => Some(Arc::new(jpeg)),
+                Err(err) => {
+                    warn!("Could not encode a JPEG frame: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Ok(mut latest) = self.shared.latest.lock() {
+            latest.image = Some(Arc::new(image));
+            latest.jpeg = jpeg;
+            latest.serial += 1;
+        }
+        self.shared.published.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_mjpeg_part() {
+        let part = mjpeg_part(b"jpeg");
+        assert!(part.starts_with(b"--ptuberframe\r\nContent-Type: image/jpeg\r\n"));
+        assert!(part.ends_with(b"\r\nContent-Length: 4\r\n\r\njpeg\r\n"));
+    }
+
+    #[test]
+    fn test_stream_only_wanted_while_watched() {
+        let settings = StreamSettings {
+            port: 0,
+            ..Default::default()
+        };
+        let server = StreamServer::start(&settings, None).unwrap();
+        assert!(!server.wanted());
+        // Publishing with nobody watching encodes nothing
+        server.publish(RgbaImage::new(2, 2));
+        assert!(server.shared.latest.lock().unwrap().jpeg.is_none());
+
+        let body = MjpegBody::new(server.shared.clone());
+        assert!(server.wanted());
+        server.publish(RgbaImage::new(2, 2));
+        assert!(server.shared.latest.lock().unwrap().jpeg.is_some());
+        drop(body);
+        assert!(!server.wanted());
+    }
+
+    #[test]
+    fn test_control_requests() {
+        let (control, actions) = std::sync::mpsc::channel();
+        let shared = Shared::new(&StreamSettings::default(), Some(control));
+        assert!(shared.request(ClipAction::Replay));
+        assert_eq!(Some(ClipAction::Replay), actions.try_iter().next());
+        drop(actions);
+        assert!(!shared.request(ClipAction::Screenshot));
+        let shared = Shared::new(&StreamSettings::default(), None);
+        assert!(!shared.request(ClipAction::Screenshot));
+    }
+
+    #[test]
+    fn test_clients_are_capped() {
+        let settings = StreamSettings {
+            max_clients: 2,
+            ..Default::default()
+        };
+        let shared = Shared::new(&settings, None);
+        assert!(shared.admit());
+        assert!(shared.admit());
+        assert!(!shared.admit());
+        shared.leave();
+        assert!(shared.admit());
+    }
+
+    #[test]
+    fn test_stalled_stream_is_not_wanted() {
+        let shared = Arc::new(Shared::new(&StreamSettings::default(), None));
+        let server = StreamServer {
+            shared: shared.clone(),
+        };
+        let body = MjpegBody::new(shared.clone());
+        assert!(server.wanted());
+        let stalled = Instant::now() - STALL_TIMEOUT;
+        shared.streams.lock().unwrap().insert(body.id, stalled);
+        assert!(!server.wanted());
+        drop(body);
+        assert!(shared.streams.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_png_endpoint() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let shared = Arc::new(Shared::new(&StreamSettings::default(), None));
+        let stream_server = StreamServer {
+            shared: shared.clone(),
+        };
+        thread::spawn(move || {
+            let request = server.recv().unwrap();
+            handle(request, shared).unwrap();
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /frame.png HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        while !stream_server.wanted() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        stream_server.publish(RgbaImage::new(3, 2));
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).unwrap();
+        let start = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert!(response.starts_with(b"HTTP/1.1 200"));
+        let png = image::load_from_memory(&response[start..]).unwrap();
+        assert_eq!((3, 2), (png.width(), png.height()));
+    }
+}
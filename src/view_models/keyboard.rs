@@ -26,6 +26,9 @@ impl KeysViewModel for KeyboardViewModel {
     fn key_released(&mut self, key: &ButtonOrKey) {
         self.model.key_released(key);
     }
+    fn keys_pressed(&self) -> Vec<ButtonOrKey> {
+        self.model.keys_pressed()
+    }
 
     fn keyboard_state(&self) -> KeyboardState {
         let keys = self.model.keys_pressed();
@@ -1,28 +1,36 @@
 mod keyboard;
 mod mouse;
+mod snapshot;
 mod traits;
 mod types;
 
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use sfml::system::Vector2f;
 use log::debug;
 
 use self::keyboard::KeyboardViewModel;
+use crate::clock;
 use crate::ButtonOrKey;
 use self::mouse::MouseViewModel;
+pub(crate) use self::snapshot::InputSnapshot;
 pub(crate) use self::traits::{DeviceViewModel, KeysViewModel};
 pub(crate) use self::types::{KeyboardState, MouseButtonState};
 use super::{DeviceButton, DeviceEvent, Keyboard, KeyboardEvent, MouseModel, DeviceType};
 
 pub struct DeviceViewModelImpl {
     view_model: Arc<Mutex<MouseViewModel>>,
+    last_input: Mutex<Instant>,
 }
 
 impl DeviceViewModelImpl {
     pub fn new() -> Self {
         let view_model = Arc::new(Mutex::new(MouseViewModel::new()));
-        Self { view_model }
+        Self {
+            view_model,
+            last_input: Mutex::new(clock::now()),
+        }
     }
 
     pub fn position(&self) -> Vector2f {
@@ -41,7 +49,30 @@ impl DeviceViewModelImpl {
         }
     }
 
+    pub fn buttons_pressed(&self) -> Vec<DeviceButton> {
+        if let Ok(view_model) = self.view_model.lock() {
+            view_model.buttons_pressed()
+        } else {
+            Default::default()
+        }
+    }
+
+    pub fn device_type(&self) -> DeviceType {
+        if let Ok(view_model) = self.view_model.lock() {
+            view_model.device_type()
+        } else {
+            Default::default()
+        }
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input.lock().map(|last| *last).unwrap_or_else(|_| clock::now())
+    }
+
     pub fn handle_event(&self, event: &DeviceEvent) {
+        if let Ok(mut last_input) = self.last_input.lock() {
+            *last_input = clock::now();
+        }
         if let Ok(mut view_model) = self.view_model.lock() {
             match event {
                 DeviceEvent::ButtonPressed(b) => view_model.button_pressed(b),
@@ -70,12 +101,16 @@ impl DeviceViewModelImpl {
 
 pub struct KeyboardViewModelImpl {
     view_model: Arc<Mutex<KeyboardViewModel>>,
+    last_input: Mutex<Instant>,
 }
 
 impl KeyboardViewModelImpl {
     pub fn new() -> Self {
         let view_model = Arc::new(Mutex::new(KeyboardViewModel::new()));
-        Self { view_model }
+        Self {
+            view_model,
+            last_input: Mutex::new(clock::now()),
+        }
     }
 
     pub fn keyboard_state(&self) -> KeyboardState {
@@ -86,7 +121,22 @@ impl KeyboardViewModelImpl {
         }
     }
 
+    pub fn keys_pressed(&self) -> Vec<ButtonOrKey> {
+        if let Ok(view_model) = self.view_model.lock() {
+            view_model.keys_pressed()
+        } else {
+            Default::default()
+        }
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input.lock().map(|last| *last).unwrap_or_else(|_| clock::now())
+    }
+
     pub fn handle_event(&self, event: &KeyboardEvent) {
+        if let Ok(mut last_input) = self.last_input.lock() {
+            *last_input = clock::now();
+        }
         if let Ok(mut view_model) = self.view_model.lock() {
             match event {
                 KeyboardEvent::KeyPressed(k) => view_model.key_pressed(&ButtonOrKey::Key(*k)),
@@ -44,6 +44,9 @@ impl DeviceViewModel for MouseViewModel {
     fn button_released(&mut self, button: &DeviceButton) {
         self.model.button_released(button);
     }
+    fn buttons_pressed(&self) -> Vec<DeviceButton> {
+        self.model.buttons_pressed()
+    }
 
     fn button_state(&self) -> MouseButtonState {
         let buttons = self.model.buttons_pressed();
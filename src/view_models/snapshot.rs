@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::clock;
+use crate::expression::{DeviceKind, InputState};
+use crate::ButtonOrKey;
+
+use super::{DeviceType, DeviceViewModelImpl, KeyboardViewModelImpl};
+
+/// Input state captured once per frame so every layer condition sees the same thing
+#[derive(Debug, Clone)]
+pub struct InputSnapshot {
+    keys: HashSet<String>,
+    buttons: HashSet<String>,
+    mouse: HashSet<String>,
+    device: DeviceKind,
+    idle: Duration,
+}
+
+fn input_name(input: impl std::fmt::Debug) -> String {
+    format!("{:?}", input).to_lowercase()
+}
+
+impl InputSnapshot {
+    pub fn capture(keyboard: &KeyboardViewModelImpl, mouse: &DeviceViewModelImpl) -> Self {
+        let (keys, buttons) = keyboard
+            .keys_pressed()
+            .into_iter()
+            .partition::<Vec<_>, _>(|key| matches!(key, ButtonOrKey::Key(_)));
+        let name = |key: ButtonOrKey| match key {
+            ButtonOrKey::Key(key) => input_name(key),
+            ButtonOrKey::Button(button) => input_name(button),
+        };
+        let device = match mouse.device_type() {
+            DeviceType::Mouse => DeviceKind::Mouse,
+            DeviceType::GamePad => DeviceKind::Gamepad,
+        };
+        let last_input = keyboard.last_input().max(mouse.last_input());
+        Self {
+            keys: keys.into_iter().map(name).collect(),
+            buttons: buttons.into_iter().map(name).collect(),
+            mouse: mouse.buttons_pressed().into_iter().map(input_name).collect(),
+            device,
+            idle: clock::now().saturating_duration_since(last_input),
+        }
+    }
+}
+
+impl InputState for InputSnapshot {
+    fn key_held(&self, name: &str) -> bool {
+        self.keys.contains(name)
+    }
+
+    fn mouse_held(&self, name: &str) -> bool {
+        self.mouse.contains(name)
+    }
+
+    fn button_held(&self, name: &str) -> bool {
+        self.buttons.contains(name)
+    }
+
+    fn device(&self) -> DeviceKind {
+        self.device
+    }
+
+    fn idle(&self) -> Duration {
+        self.idle
+    }
+}
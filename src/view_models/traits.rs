@@ -12,6 +12,7 @@ pub trait DeviceViewModel {
 
     fn button_pressed(&mut self, button: &Self::Button);
     fn button_released(&mut self, button: &Self::Button);
+    fn buttons_pressed(&self) -> Vec<Self::Button>;
 
     fn button_state(&self) -> Self::ButtonState;
 }
@@ -22,6 +23,7 @@ pub trait KeysViewModel {
 
     fn key_pressed(&mut self, key: &Self::Key);
     fn key_released(&mut self, key: &Self::Key);
+    fn keys_pressed(&self) -> Vec<Self::Key>;
 
     fn keyboard_state(&self) -> Self::KeyboardState;
 }